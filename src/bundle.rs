@@ -0,0 +1,292 @@
+use crate::utils::{find_files, inflate_input, input_name, is_input};
+use anyhow::{ensure, Context, Result};
+use clap::Args;
+use log::info;
+use std::collections::HashSet;
+use std::fs::{create_dir_all, read, read_to_string, write};
+use std::path::{Path, PathBuf};
+use tabled::{Table, Tabled};
+use tempfile::TempDir;
+
+#[derive(Debug, Args)]
+pub(super) struct BundleArgs {
+    /// directory containing the testcases or path to the testcase(*.in) (./testcases/input),
+    /// or the bundles with --split (./testcases/bundle)
+    #[arg(value_name = "TESTCASES")]
+    testcases: Vec<PathBuf>,
+
+    /// recursively search for testcases
+    #[arg(short, long, default_value_t = false)]
+    recursive: bool,
+
+    /// number of cases per bundle (all cases in one bundle if omitted)
+    #[arg(short = 'k', long, value_name = "K", value_parser = clap::value_parser!(u32).range(1..))]
+    size: Option<u32>,
+
+    /// prefix of the bundle names (<NAME>_000.in, <NAME>_001.in, ...)
+    #[arg(long, default_value = "bundle")]
+    name: String,
+
+    /// split bundles made by kuroe bundle back into the original cases
+    #[arg(long, default_value_t = false, conflicts_with_all = ["size", "name"])]
+    split: bool,
+
+    /// output directory (./testcases/bundle, or ./testcases/input with --split)
+    #[arg(short, long)]
+    outdir: Option<PathBuf>,
+}
+
+/// bundle と同じディレクトリの {name}.parts（元のケース名と行数の一覧）
+fn parts_path(bundle: &Path) -> PathBuf {
+    bundle.with_file_name(format!("{}.parts", input_name(bundle)))
+}
+
+/// 末尾に改行がなければ付け加える
+fn with_newline(mut content: Vec<u8>) -> Vec<u8> {
+    if !content.is_empty() && !content.ends_with(b"\n") {
+        content.push(b'\n');
+    }
+    content
+}
+
+/// outdir 直下のファイル名として使えるケース名かどうか
+fn is_safe_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// ケース名が重複または不正であればエラーとする（分割した際に互いを上書きしたり outdir の外に書き込んだりしないように）
+fn check_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let mut seen = HashSet::new();
+    for name in names {
+        ensure!(is_safe_name(name), "invalid case name {name:?}");
+        ensure!(seen.insert(name), "case name {name:?} appears twice");
+    }
+    Ok(())
+}
+
+/// cases を 1 つのファイルにまとめる
+/// 1 行目はケース数 T，続いて各ケースをそのまま（改行コードも含めて）連結する
+/// 分割できるよう，元のケース名と行数を {name}.parts に記録する
+fn bundle(cases: &[PathBuf], output: &Path) -> Result<()> {
+    let inflate_dir = TempDir::new()?;
+    let mut content = format!("{}\n", cases.len()).into_bytes();
+    let mut parts = String::new();
+    for case in cases {
+        let input = inflate_input(case, inflate_dir.path())?;
+        let case_content =
+            with_newline(read(&input).with_context(|| format!("failed to read {case:?}"))?);
+        let lines = case_content.iter().filter(|&&byte| byte == b'\n').count();
+        parts += &format!("{} {lines}\n", input_name(case));
+        content.extend(case_content);
+    }
+    write(output, content)?;
+    write(parts_path(output), parts)?;
+    Ok(())
+}
+
+/// bundle の {name}.parts から (ケース名, 行数) の一覧を読み込む
+fn read_parts(bundle: &Path) -> Result<Vec<(String, usize)>> {
+    let parts_path = parts_path(bundle);
+    let parts = read_to_string(&parts_path)
+        .with_context(|| format!("failed to read {parts_path:?} (made by kuroe bundle)"))?;
+    let parts = parts
+        .lines()
+        .map(|line| {
+            let (name, lines) = line
+                .rsplit_once(' ')
+                .with_context(|| format!("invalid line {line:?} in {parts_path:?}"))?;
+            let lines = lines
+                .parse::<usize>()
+                .with_context(|| format!("invalid line {line:?} in {parts_path:?}"))?;
+            Ok((name.to_string(), lines))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check_names(parts.iter().map(|(name, _)| name.as_str()))
+        .with_context(|| format!("invalid {parts_path:?}"))?;
+    Ok(parts)
+}
+
+/// bundle を元のケースに分割し，分割したケースのパスを返す
+fn split(bundle: &Path, outdir: &Path) -> Result<Vec<PathBuf>> {
+    let parts_path = parts_path(bundle);
+    let parts = read_parts(bundle)?;
+
+    // \r などを含めて元のケースの内容をそのまま復元するため，バイト列のまま \n で区切る
+    let inflate_dir = TempDir::new()?;
+    let content = read(inflate_input(bundle, inflate_dir.path())?)?;
+    let mut lines = content.split_inclusive(|&byte| byte == b'\n');
+    let count = String::from_utf8_lossy(lines.next().unwrap_or_default());
+    let count = count.trim();
+    ensure!(
+        count == parts.len().to_string(),
+        "{bundle:?} has T = {count:?}, but {parts_path:?} lists {} case(s)",
+        parts.len()
+    );
+
+    let mut cases = Vec::new();
+    for (name, len) in parts {
+        let case_lines: Vec<&[u8]> = lines.by_ref().take(len).collect();
+        ensure!(
+            case_lines.len() == len,
+            "{bundle:?} is shorter than {parts_path:?} says"
+        );
+
+        let case = outdir.join(format!("{name}.in"));
+        write(&case, with_newline(case_lines.concat()))?;
+        cases.push(case);
+    }
+    ensure!(
+        lines.next().is_none(),
+        "{bundle:?} is longer than {parts_path:?} says"
+    );
+    Ok(cases)
+}
+
+pub(super) fn root(args: BundleArgs) -> Result<()> {
+    info!("{:#?}", args);
+
+    let bases = if args.testcases.is_empty() {
+        vec![PathBuf::from(if args.split {
+            "./testcases/bundle"
+        } else {
+            "./testcases/input"
+        })]
+    } else {
+        args.testcases.clone()
+    };
+    let testcases = {
+        let mut testcases = Vec::new();
+        for base in &bases {
+            for target in find_files(base, args.recursive)? {
+                if is_input(&target) {
+                    testcases.push(target);
+                }
+            }
+        }
+        testcases.sort();
+        testcases
+    };
+    if testcases.is_empty() {
+        println!("no testcase found!");
+        return Ok(());
+    }
+    info!("testcases = {testcases:#?}");
+
+    let outdir = args.outdir.clone().unwrap_or_else(|| {
+        PathBuf::from(if args.split {
+            "./testcases/input"
+        } else {
+            "./testcases/bundle"
+        })
+    });
+    if !outdir.exists() {
+        create_dir_all(&outdir)?;
+    }
+
+    #[derive(Tabled)]
+    struct Result {
+        bundle: String,
+        cases: usize,
+    }
+    let mut results = Vec::new();
+
+    if args.split {
+        // 複数の bundle が同名のケースを含む場合は，書き込む前にエラーとする
+        let mut names = Vec::new();
+        for target in &testcases {
+            names.extend(read_parts(target)?.into_iter().map(|(name, _)| name));
+        }
+        check_names(names.iter().map(String::as_str))?;
+
+        for target in &testcases {
+            let cases = split(target, &outdir)?;
+            info!("[SPLIT] {target:?} -> {cases:?}");
+            results.push(Result {
+                bundle: format!("{target:?}"),
+                cases: cases.len(),
+            });
+        }
+    } else {
+        // 別のディレクトリの同名のケースは分割した際に互いを上書きしてしまう
+        let names: Vec<String> = testcases.iter().map(|case| input_name(case)).collect();
+        check_names(names.iter().map(String::as_str))?;
+        let size = args.size.map_or(testcases.len(), |size| size as usize);
+        for (i, cases) in testcases.chunks(size).enumerate() {
+            let output = outdir.join(format!("{}_{i:03}.in", args.name));
+            bundle(cases, &output)?;
+            info!("[BUNDLE] {cases:?} -> {output:?}");
+            results.push(Result {
+                bundle: format!("{output:?}"),
+                cases: cases.len(),
+            });
+        }
+    }
+
+    println!("{}", Table::new(results));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_and_split() {
+        let dir = TempDir::new().unwrap();
+        let a = dir.path().join("a_000.in");
+        let b = dir.path().join("b_000.in");
+        let c = dir.path().join("c_000.in");
+        write(&a, "3\n1 2 3\n").unwrap();
+        write(&b, "1\n5").unwrap();
+        write(&c, "").unwrap();
+
+        let output = dir.path().join("bundle_000.in");
+        bundle(&[a, b, c], &output).unwrap();
+        assert_eq!(read_to_string(&output).unwrap(), "3\n3\n1 2 3\n1\n5\n");
+        assert_eq!(
+            read_to_string(dir.path().join("bundle_000.parts")).unwrap(),
+            "a_000 2\nb_000 2\nc_000 0\n"
+        );
+
+        let outdir = dir.path().join("split");
+        create_dir_all(&outdir).unwrap();
+        let cases = split(&output, &outdir).unwrap();
+        assert_eq!(
+            cases,
+            vec![
+                outdir.join("a_000.in"),
+                outdir.join("b_000.in"),
+                outdir.join("c_000.in")
+            ]
+        );
+        assert_eq!(read_to_string(&cases[0]).unwrap(), "3\n1 2 3\n");
+        assert_eq!(read_to_string(&cases[1]).unwrap(), "1\n5\n");
+        assert_eq!(read_to_string(&cases[2]).unwrap(), "");
+
+        // 改行コードも含めて元の内容を復元する
+        let d = dir.path().join("d_000.in");
+        write(&d, "1\r\n2 3\r\n").unwrap();
+        bundle(std::slice::from_ref(&d), &output).unwrap();
+        let cases = split(&output, &outdir).unwrap();
+        assert_eq!(read(&cases[0]).unwrap(), b"1\r\n2 3\r\n");
+
+        // outdir の外を指すケース名
+        write(dir.path().join("bundle_000.parts"), "../d_000 2\n").unwrap();
+        assert!(split(&output, &outdir).is_err());
+
+        // T とケースの一覧が一致しない
+        write(dir.path().join("bundle_000.parts"), "d_000 2\n").unwrap();
+        write(&output, "2\n1\r\n2 3\r\n").unwrap();
+        assert!(split(&output, &outdir).is_err());
+    }
+
+    #[test]
+    fn test_check_names() {
+        assert!(check_names(["a_000", "b_000"]).is_ok());
+        assert!(check_names(["a_000", "a_000"]).is_err());
+        assert!(check_names(["sub/a_000"]).is_err());
+        assert!(check_names([".."]).is_err());
+        assert!(check_names([""]).is_err());
+    }
+}
@@ -1,41 +1,186 @@
-use crate::language::{compile_and_get_runstep, ExecuteStatus, Language};
-use crate::utils::{find_files, make_languages};
-use anyhow::{Context, Result};
+use crate::language::{compile_and_get_runstep, CommandStep, ExecuteStatus, Language};
+use crate::solve;
+use crate::utils::{
+    find_duplicates, find_files, gzip_file, inflate_input, input_size, is_input, make_languages,
+};
+use anyhow::{bail, ensure, Context, Result};
 use clap::Args;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{info, warn};
-use std::fs::{create_dir_all, File};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::env::current_dir;
+use std::fs::{
+    copy, create_dir_all, read_to_string, remove_dir, remove_dir_all, remove_file, write, File,
+    OpenOptions,
+};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tabled::{Table, Tabled};
-use tempfile::TempDir;
+use tempfile::{TempDir, TempPath};
+
+pub(crate) mod manifest;
+mod recipe;
+pub(crate) mod schema;
+mod stress;
 
 #[derive(Debug, Args)]
 pub(super) struct GenerateArgs {
     /// directory containing the generator or path to the generator
-    #[arg(value_name = "GENERATOR", required = true)]
+    #[arg(value_name = "GENERATOR", required_unless_present_any = ["recipe", "manual"])]
     generators: Vec<PathBuf>,
 
+    /// copy hand-written cases (*.txt, *.in) under DIR verbatim as <NAME>_000.in
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "./manual")]
+    manual: Option<PathBuf>,
+
+    /// toml file listing generator runs (generator, count, seed, args, name)
+    #[arg(long, value_name = "PATH")]
+    recipe: Option<PathBuf>,
+
     /// recursively search for generators
     #[arg(short, long, default_value_t = false)]
     recursive: bool,
 
-    ///
+    /// output directory
     #[arg(short, long, default_value = "./testcases/input")]
     outdir: PathBuf,
 
-    /// number of generation per generator. Specifying by filename has higher priority
-    #[arg(short = 'n', long, default_value_t = 1
-    , value_parser = clap::value_parser!(u32).range(1..))]
-    count: u32,
+    /// number of generation per generator. Specifying by filename has higher priority.
+    /// GENERATOR=N (comma separated) sets the count of a single generator and overrides the filename
+    #[arg(
+        short = 'n',
+        long,
+        value_name = "N|GENERATOR=N",
+        default_value = "1",
+        value_delimiter = ',',
+        value_parser = parse_count_spec
+    )]
+    count: Vec<CountSpec>,
+
+    /// file listing GENERATOR=N entries separated by whitespace or commas (lines starting with # are ignored)
+    #[arg(long, value_name = "PATH")]
+    count_map: Option<PathBuf>,
 
     /// seed, seed+1, seed+2, ..., seed+(n-1)
     #[arg(short, long, default_value_t = 0, required = false
     , value_parser = clap::value_parser!(u32).range(0..))]
     seed: u32,
 
-    /// timelimit for generating answer
+    /// comma separated list of seeds. Each generator is run once per seed (overrides --count and --seed)
+    #[arg(
+        long,
+        value_name = "SEED,...",
+        value_delimiter = ',',
+        conflicts_with = "seed_file"
+    )]
+    seeds: Vec<u32>,
+
+    /// file listing seeds separated by whitespace or commas (lines starting with # are ignored)
+    #[arg(long, value_name = "PATH")]
+    seed_file: Option<PathBuf>,
+
+    /// how to pass the seed to the generator: argv, stdin, env[:NAME] (NAME defaults to SEED)
+    /// or testlib (appended as --testcase-index=SEED after the other arguments)
+    #[arg(long, value_name = "MODE", default_value = "argv", value_parser = parse_seed_via)]
+    seed_via: SeedVia,
+
+    /// environment variable passed to the generators (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env)]
+    env: Vec<(String, String)>,
+
+    /// shell command run on each generated case before it is saved, e.g. "python3 normalize.py %(file)".
+    /// %(file) is replaced with the path of the case
+    #[arg(long, value_name = "COMMAND")]
+    post_cmd: Option<String>,
+
+    /// template of the output file name. Fields: {name}, {index}, {seed}, {params} (numbers accept zero padding such as {index:03})
+    #[arg(long, value_name = "FORMAT", default_value = "{name}_{index:03}.in")]
+    name_format: String,
+
+    /// repeat generating with random seeds and comparing --solver with --brute until they disagree
+    #[arg(long, default_value_t = false, requires_all = ["solver", "brute"])]
+    stress: bool,
+
+    /// reference solution used to write the answer of each generated case into --answer-dir
+    /// (the solver to be tested in --stress mode)
+    #[arg(long, value_name = "PATH")]
+    solver: Option<PathBuf>,
+
+    /// output directory of the answers generated by --solver
+    #[arg(long, value_name = "DIR", default_value = "./testcases/answer")]
+    answer_dir: PathBuf,
+
+    /// reference (brute force) solver for --stress mode
+    #[arg(long, value_name = "PATH", requires = "stress")]
+    brute: Option<PathBuf>,
+
+    /// maximum number of iterations in --stress mode (unlimited if omitted)
+    #[arg(long, value_name = "N", requires = "stress")]
+    iterations: Option<u64>,
+
+    /// write the cases into <OUTDIR>/subtaskN (N = SUBTASK if it is a number, otherwise <OUTDIR>/SUBTASK).
+    /// The subtask in the recipe has higher priority
+    #[arg(long, value_name = "SUBTASK")]
+    subtask: Option<String>,
+
+    /// print the planned generator runs without compiling or running anything
+    #[arg(long, default_value_t = false, conflicts_with = "stress")]
+    dry_run: bool,
+
+    /// compress the generated cases with gzip (<NAME>.in.gz)
+    #[arg(long, default_value_t = false)]
+    gzip: bool,
+
+    /// remove *.in and *.in.gz files (and logs) in the outdir before generating
+    #[arg(long, default_value_t = false, conflicts_with_all = ["skip_existing", "stress"])]
+    fresh: bool,
+
+    /// continue numbering (and seeds unless --seeds is given) after the existing cases instead of overwriting them
+    #[arg(long, default_value_t = false, conflicts_with_all = ["fresh", "skip_existing"])]
+    append: bool,
+
+    /// skip cases whose output file already exists
+    #[arg(long, default_value_t = false)]
+    skip_existing: bool,
+
+    /// the generators write any number of cases into the directory given as their last argument.
+    /// The cases are collected in name order and renamed by --name-format ({seed} is the seed of the run)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["stress", "skip_existing", "append"])]
+    multi: bool,
+
+    /// overall wall-clock budget of the generation such as 300s, 5m or 1h (seconds if no unit).
+    /// No more generator runs are started once it is exhausted
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    budget: Option<Duration>,
+
+    /// when a generator exits with an error, retry up to N times with unused subsequent seeds
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    retry: u32,
+
+    /// continue generating after a generator fails or times out (still exits with an error)
+    #[arg(long, default_value_t = false)]
+    keep_going: bool,
+
+    /// validate every generated case and delete the invalid ones
+    #[arg(long, value_name = "PATH")]
+    validator: Option<PathBuf>,
+
+    /// warn when a generated case is larger than this size (MiB)
+    #[arg(long, value_name = "MiB", default_value_t = 64.0)]
+    max_size: f64,
+
+    /// fail (instead of warning) when a generated case is empty or larger than --max-size
+    #[arg(long, default_value_t = false)]
+    strict_size: bool,
+
+    /// fail when two generated cases are byte-identical (otherwise only warn)
+    #[arg(long, default_value_t = false)]
+    no_duplicates: bool,
+
+    /// timelimit of the generators (and of --solver). A generator can override it by the filename
+    /// (<NAME>.tl<SECONDS>.<EXT>) or timelimit in the recipe
     #[arg(visible_alias = "tl", long, default_value_t = 10.0)]
     timelimit: f64,
 
@@ -50,23 +195,237 @@ pub(super) struct GenerateArgs {
     language: Vec<String>,
 }
 
-#[derive(Debug)]
+/// ジェネレータへの seed の渡し方
+#[derive(Debug, Clone, PartialEq)]
+enum SeedVia {
+    /// 最初のコマンドライン引数
+    Argv,
+    /// 標準入力
+    Stdin,
+    /// 環境変数
+    Env(String),
+    /// testlib 形式のオプション（--testcase-index=SEED）として引数の最後に渡す
+    /// registerGen は argv 全体から乱数を初期化するため，ケースごとに argv が異なれば良い
+    Testlib,
+}
+
+impl std::fmt::Display for SeedVia {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SeedVia::Argv => write!(f, "argv"),
+            SeedVia::Stdin => write!(f, "stdin"),
+            SeedVia::Env(name) => write!(f, "env:{name}"),
+            SeedVia::Testlib => write!(f, "testlib"),
+        }
+    }
+}
+
+fn parse_seed_via(s: &str) -> Result<SeedVia> {
+    match s.split_once(':') {
+        None if s == "argv" => Ok(SeedVia::Argv),
+        None if s == "stdin" => Ok(SeedVia::Stdin),
+        None if s == "testlib" => Ok(SeedVia::Testlib),
+        None if s == "env" => Ok(SeedVia::Env("SEED".to_string())),
+        Some(("env", name)) if !name.is_empty() => Ok(SeedVia::Env(name.to_string())),
+        _ => bail!("expected argv, stdin, env[:NAME] or testlib, got {s:?}"),
+    }
+}
+
+/// seed の渡し方に応じたジェネレータのコマンドライン引数
+fn seed_args(seed_via: &SeedVia, seed: u32, args: &[String]) -> Vec<String> {
+    match seed_via {
+        SeedVia::Argv => [vec![seed.to_string()], args.to_vec()].concat(),
+        SeedVia::Testlib => [args.to_vec(), vec![format!("--testcase-index={seed}")]].concat(),
+        SeedVia::Stdin | SeedVia::Env(_) => args.to_vec(),
+    }
+}
+
+/// seed の渡し方に応じて (runstep, 引数, 標準入力) を返す
+/// 標準入力で渡す場合は dir に seed を書き込んだファイルを作る
+fn pass_seed(
+    runstep: &CommandStep,
+    seed_via: &SeedVia,
+    seed: u32,
+    args: &[String],
+    dir: &Path,
+) -> Result<(CommandStep, Vec<String>, Stdio)> {
+    let seed_args = seed_args(seed_via, seed, args);
+    match seed_via {
+        SeedVia::Argv | SeedVia::Testlib => Ok((runstep.clone(), seed_args, Stdio::null())),
+        SeedVia::Stdin => {
+            let path = dir.join("seed.txt");
+            write(&path, format!("{seed}\n"))?;
+            Ok((runstep.clone(), seed_args, Stdio::from(File::open(&path)?)))
+        }
+        SeedVia::Env(name) => Ok((
+            runstep.clone().env(name, &seed.to_string()),
+            seed_args,
+            Stdio::null(),
+        )),
+    }
+}
+
+/// 小課題の出力先ディレクトリ名（数値 N なら subtaskN，それ以外はそのまま）
+fn subtask_dir(subtask: &str) -> Result<String> {
+    ensure!(
+        !subtask.is_empty() && !subtask.contains(['/', '\\']) && subtask != "..",
+        "invalid subtask {subtask:?}"
+    );
+    if subtask.chars().all(|c| c.is_ascii_digit()) {
+        Ok(format!("subtask{subtask}"))
+    } else {
+        Ok(subtask.to_string())
+    }
+}
+
+/// 300s, 5m, 1h, 300 (秒) の形式の時間を読む
+fn parse_duration(s: &str) -> Result<Duration> {
+    let (value, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let value = value
+        .parse::<f64>()
+        .with_context(|| format!("invalid duration {s:?}"))?;
+    ensure!(value >= 0.0, "duration must not be negative: {s:?}");
+
+    let seconds = match unit {
+        "ms" => value / 1000.0,
+        "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => bail!("unknown unit {unit:?} in {s:?} (expected ms, s, m or h)"),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+fn parse_env(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => bail!("expected KEY=VALUE, got {s:?}"),
+    }
+}
+
+/// ジェネレータの runstep に --env の環境変数を設定する
+fn with_envs(runstep: CommandStep, envs: &[(String, String)]) -> CommandStep {
+    envs.iter()
+        .fold(runstep, |runstep, (key, value)| runstep.env(key, value))
+}
+
+/// --post-cmd の実行コマンド
+/// %(file) を "$1" に置き換えて sh -c で実行するので，パスの空白等をエスケープする必要はない
+fn post_cmd_step(post_cmd: &str) -> Result<CommandStep> {
+    ensure!(
+        post_cmd.contains("%(file)"),
+        "--post-cmd must contain %(file): {post_cmd:?}"
+    );
+    Ok(CommandStep::new(
+        "sh".to_string(),
+        vec![
+            "-c".to_string(),
+            post_cmd.replace("%(file)", "\"$1\""),
+            "sh".to_string(),
+        ],
+    ))
+}
+
+/// seed より後の未使用の seed を返し，使用済みとして記録する
+fn next_unused_seed(seed: u32, used: &mut HashSet<u32>) -> u32 {
+    let mut next = seed.wrapping_add(1);
+    while used.contains(&next) {
+        next = next.wrapping_add(1);
+    }
+    used.insert(next);
+    next
+}
+
+/// --count の要素
+#[derive(Debug, Clone, PartialEq)]
+enum CountSpec {
+    /// 全てのジェネレータの生成数
+    All(u32),
+    /// 特定のジェネレータ（ファイル名またはパス）の生成数
+    For(String, u32),
+}
+
+fn parse_count_spec(s: &str) -> Result<CountSpec> {
+    let (name, count) = match s.rsplit_once('=') {
+        Some((name, count)) => (Some(name), count),
+        None => (None, s),
+    };
+    let count = count
+        .parse::<u32>()
+        .with_context(|| format!("invalid count {s:?}"))?;
+    ensure!(count > 0, "count must be positive: {s:?}");
+
+    match name {
+        Some(name) if !name.is_empty() => Ok(CountSpec::For(name.to_string(), count)),
+        Some(_) => bail!("generator name is empty: {s:?}"),
+        None => Ok(CountSpec::All(count)),
+    }
+}
+
+/// name がジェネレータ path を指すかどうか（ファイル名またはパスで比較する）
+fn matches_generator(name: &str, path: &Path) -> bool {
+    path.file_name().is_some_and(|file_name| file_name == name) || path == Path::new(name)
+}
+
+/// --count-map のファイルを読み込む（空白またはカンマ区切り，# 以降はコメント）
+fn read_count_map(path: &Path) -> Result<Vec<CountSpec>> {
+    let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+
+    let mut specs = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap();
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+            let spec = parse_count_spec(token).with_context(|| format!("in {path:?}"))?;
+            specs.push(spec);
+        }
+    }
+
+    Ok(specs)
+}
+
+#[derive(Debug, Clone)]
 struct GenFileInfo {
     path: PathBuf,
     name: String,
     count: Option<u32>,
+    /// 開始 seed（レシピで指定された場合）
+    seed: Option<u32>,
+    /// seed の後に渡す追加の引数
+    args: Vec<String>,
+    /// 出力先の outdir からの相対パス（subtaskN など）
+    subtask: Option<String>,
+    /// このジェネレータのタイムリミット（秒）。None なら --timelimit
+    timelimit: Option<f64>,
 }
 
 impl GenFileInfo {
     /// hoge.{count}.ext を解釈する
     /// count が u32 としてパースできる場合は name = "hoge"
     /// count が u32 としてパースできない場合は name = "hoge.{count}"
+    /// hoge.{count}.tl{seconds}.ext, hoge.tl{seconds}.ext の場合はこのジェネレータのタイムリミットとする
     fn new(path: &Path) -> Result<Self> {
         let stem = path.file_stem().unwrap().to_string_lossy().to_string();
 
+        // timelimit の解決
+        let re = Regex::new(r"^(.+)\.tl(\d+(?:\.\d+)?)$").unwrap();
+        let (stem, timelimit) = match re.captures(&stem) {
+            Some(caps) => {
+                let timelimit = caps[2].parse::<f64>()?;
+                ensure!(timelimit > 0.0, "timelimit of {path:?} must be positive");
+                (caps[1].to_string(), Some(timelimit))
+            }
+            None => (stem, None),
+        };
+
         // count の解決
         let parts: Vec<&str> = stem.rsplitn(2, '.').collect();
-        let count = parts.get(0).and_then(|s| s.parse::<u32>().ok());
+        let count = parts.first().and_then(|s| s.parse::<u32>().ok());
         let name = if count.is_some() {
             parts
                 .get(1)
@@ -80,129 +439,1064 @@ impl GenFileInfo {
             path: path.to_path_buf(),
             name,
             count,
+            seed: None,
+            args: Vec::new(),
+            subtask: None,
+            timelimit,
         })
     }
+
+    /// このジェネレータのタイムリミット（秒）
+    fn timelimit(&self, args: &GenerateArgs) -> f64 {
+        self.timelimit.unwrap_or(args.timelimit)
+    }
+
+    /// このジェネレータの出力先ディレクトリ
+    fn outdir(&self, args: &GenerateArgs) -> PathBuf {
+        match self.subtask {
+            Some(ref subtask) => args.outdir.join(subtask),
+            None => args.outdir.clone(),
+        }
+    }
+
+    /// このジェネレータに渡す seed の列
+    /// --seeds が指定されていればそれを用い，なければ seed, seed+1, ..., seed+(count-1)
+    fn seeds(&self, args: &GenerateArgs) -> Vec<u32> {
+        if !args.seeds.is_empty() {
+            return args.seeds.clone();
+        }
+
+        // GENERATOR=N > ファイル名（レシピ）の指定 > N の順に優先する
+        let mapped = args.count.iter().rev().find_map(|spec| match spec {
+            CountSpec::For(name, count) if matches_generator(name, &self.path) => Some(*count),
+            _ => None,
+        });
+        let default = args.count.iter().rev().find_map(|spec| match spec {
+            CountSpec::All(count) => Some(*count),
+            _ => None,
+        });
+        let count = mapped.or(self.count).unwrap_or(default.unwrap_or(1));
+        let seed = self.seed.unwrap_or(args.seed);
+        (0..count).map(|i| seed.wrapping_add(i)).collect()
+    }
+}
+
+/// seed の一覧を読み込む（空白またはカンマ区切り，# 以降はコメント）
+fn read_seed_file(path: &Path) -> Result<Vec<u32>> {
+    let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+
+    let mut seeds = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap();
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+            let seed = token
+                .parse::<u32>()
+                .with_context(|| format!("invalid seed {token:?} in {path:?}"))?;
+            seeds.push(seed);
+        }
+    }
+    ensure!(!seeds.is_empty(), "no seed found in {path:?}");
+
+    Ok(seeds)
+}
+
+/// --name-format のテンプレートから出力ファイル名を作る
+/// params は追加の引数を _ で連結したもの
+fn format_name(
+    format: &str,
+    name: &str,
+    index: u32,
+    seed: u32,
+    params: &[String],
+) -> Result<String> {
+    let re = Regex::new(r"\{(\w+)(?::0(\d+))?\}").unwrap();
+
+    let mut formatted = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(format) {
+        let whole = caps.get(0).unwrap();
+        formatted.push_str(&format[last..whole.start()]);
+        last = whole.end();
+
+        let width = caps
+            .get(2)
+            .map_or(0, |width| width.as_str().parse::<usize>().unwrap_or(0));
+        let value = match &caps[1] {
+            "name" => name.to_string(),
+            "params" => params.join("_"),
+            "index" => format!("{index:0width$}"),
+            "seed" => format!("{seed:0width$}"),
+            field => bail!("unknown field {{{field}}} in name format {format:?}"),
+        };
+        formatted.push_str(&value);
+    }
+    formatted.push_str(&format[last..]);
+
+    Ok(formatted)
+}
+
+/// ケースの生成時の標準エラー出力の保存先 {outdir}/logs/{name}.err
+fn stderr_log_path(case: &Path) -> PathBuf {
+    let name = case.file_stem().unwrap().to_string_lossy().to_string();
+    case.parent()
+        .unwrap_or(Path::new(""))
+        .join("logs")
+        .join(format!("{name}.err"))
+}
+
+/// index 番目（seed で生成する）ケースの出力先
+fn case_path(target: &GenFileInfo, args: &GenerateArgs, index: u32, seed: u32) -> Result<PathBuf> {
+    let name = format_name(&args.name_format, &target.name, index, seed, &target.args)?;
+    let name = if args.gzip { name + ".gz" } else { name };
+    Ok(target.outdir(args).join(name))
+}
+
+/// --name-format が {index} か {seed} を含む（ケースごとに異なる名前になる）かどうか
+fn is_numbered(format: &str) -> bool {
+    let re = Regex::new(r"\{(index|seed)(?::0\d+)?\}").unwrap();
+    re.is_match(format)
+}
+
+/// --append: index 0 から連続して既に存在するケースの数
+fn existing_cases(target: &GenFileInfo, args: &GenerateArgs) -> Result<u32> {
+    // 番号を含まない名前では同じファイルが存在し続けるため数え終わらない
+    ensure!(
+        is_numbered(&args.name_format),
+        "name format {:?} must contain {{index}} or {{seed}} with --append",
+        args.name_format
+    );
+    let seed = target.seed.unwrap_or(args.seed);
+    let mut count: u32 = 0;
+    while case_path(target, args, count, seed.wrapping_add(count))?.exists() {
+        count = count
+            .checked_add(1)
+            .context("too many existing cases to append")?;
+    }
+    Ok(count)
+}
+
+/// target の各 seed と出力先のパスを返す
+/// --append の場合は既存のケースの続きから番号を振る（明示された --seeds 以外は seed もずらす）
+fn output_paths(target: &GenFileInfo, args: &GenerateArgs) -> Result<Vec<(u32, PathBuf)>> {
+    let offset = if args.append {
+        existing_cases(target, args)?
+    } else {
+        0
+    };
+
+    let mut paths = Vec::new();
+    for (i, seed) in target.seeds(args).into_iter().enumerate() {
+        let index = offset
+            .checked_add(i as u32)
+            .context("too many cases to append")?;
+        let seed = if args.seeds.is_empty() {
+            seed.wrapping_add(offset)
+        } else {
+            seed
+        };
+        let path = case_path(target, args, index, seed)?;
+        // 番号が飛んでいる場合に既存のケースを上書きしないようにする
+        ensure!(
+            !args.append || !path.exists(),
+            "{path:?} already exists (--append does not overwrite existing cases)"
+        );
+        paths.push((seed, path));
+    }
+    Ok(paths)
+}
+
+/// 出力先が衝突する（別のディレクトリの同名のジェネレータ等が互いの出力を上書きする）場合はエラーとする
+/// manual は手書きのケースの (コピー先, コピー元)
+fn check_name_collisions(
+    generators: &[GenFileInfo],
+    manual: &[(PathBuf, PathBuf)],
+    args: &GenerateArgs,
+) -> Result<()> {
+    let mut owners: HashMap<PathBuf, &Path> = HashMap::new();
+    for target in generators {
+        for (_, path) in output_paths(target, args)? {
+            if let Some(other) = owners.insert(path.clone(), &target.path) {
+                bail!(
+                    "{path:?} would be generated twice (by {other:?} and {:?}). Rename the generator or set name in the recipe",
+                    target.path
+                );
+            }
+        }
+    }
+    for (path, from) in manual {
+        if let Some(other) = owners.insert(path.clone(), from) {
+            bail!(
+                "{path:?} would be generated twice (by {other:?} and {from:?}). Rename the manual case"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// --dry-run: 各ジェネレータの実行予定（seed, 引数, 出力先）を表示する
+fn print_plan(generators: &[GenFileInfo], args: &GenerateArgs) -> Result<()> {
+    #[derive(Tabled)]
+    struct Plan {
+        generator: String,
+        seed: u32,
+        arguments: String,
+        output: String,
+    }
+    let mut plans = Vec::new();
+    let mut skipped = 0;
+    for target in generators {
+        for (seed, output_path) in output_paths(target, args)? {
+            let output = if args.skip_existing && output_path.exists() {
+                skipped += 1;
+                format!("{output_path:?} (skip)")
+            } else if args.multi {
+                format!("{:?} (multiple)", target.outdir(args))
+            } else {
+                format!("{output_path:?}")
+            };
+            plans.push(Plan {
+                generator: format!("{:?}", target.path),
+                seed,
+                arguments: seed_args(&args.seed_via, seed, &target.args).join(" "),
+                output,
+            });
+        }
+    }
+
+    println!("{}", Table::new(&plans));
+    println!(
+        "{} {} from {} generator(s) will be generated",
+        plans.len() - skipped,
+        if args.multi { "run(s)" } else { "case(s)" },
+        generators.len()
+    );
+    Ok(())
+}
+
+/// 一時ファイル output を後処理して output_path に保存し，最終的な status を返す
+/// 後処理は保存前の一時ファイルに対してカレントディレクトリで行い，標準エラー出力はジェネレータのログに追記する
+/// 失敗した場合は以前の生成結果も古いので残さない
+fn save_case(
+    output: TempPath,
+    output_path: &Path,
+    status: ExecuteStatus,
+    post_step: Option<&CommandStep>,
+    log_path: &Path,
+    args: &GenerateArgs,
+) -> Result<ExecuteStatus> {
+    let status = match post_step {
+        Some(post_step) if status.success() => post_step
+            .execute(
+                current_dir()?,
+                vec![output.to_string_lossy().to_string()],
+                Stdio::null(),
+                Stdio::null(),
+                OpenOptions::new().append(true).open(log_path)?,
+                Duration::from_secs_f64(args.timelimit),
+            )
+            .with_context(|| format!("failed to run --post-cmd on {output_path:?}"))?,
+        _ => status,
+    };
+
+    let parent = output_path.parent().unwrap_or(Path::new(""));
+    if status.success() && args.gzip {
+        let compressed = tempfile::Builder::new()
+            .prefix(".")
+            .suffix(".tmp")
+            .tempfile_in(parent)?;
+        gzip_file(&output, compressed.path())?;
+        compressed.persist(output_path)?;
+    } else if status.success() {
+        output.persist(output_path)?;
+    } else if output_path.exists() {
+        // 一時ファイルは drop で削除される
+        remove_file(output_path)?;
+    }
+    Ok(status)
+}
+
+/// 生成した各ケースに直ちに行う検査（--validator）と解答の生成（--solver）
+struct CaseChecks {
+    validator: Option<(TempDir, CommandStep)>,
+    solver: Option<(TempDir, CommandStep)>,
+}
+
+/// ケースの検査の結果
+#[derive(Debug, Default)]
+struct CaseCheck {
+    /// 不正と判定された場合の validator のメッセージ（ケースは削除済み）
+    invalid: Option<String>,
+    /// --solver による解答の (status, 出力先)
+    answer: Option<(ExecuteStatus, PathBuf)>,
+    /// 生成の失敗として報告する内容
+    failure: Option<String>,
+}
+
+impl CaseCheck {
+    fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+impl CaseChecks {
+    /// case を検査し，不正でなければ解答を生成する．不正なケースはその場で削除する
+    /// timelimit は validator のタイムリミット
+    fn run(&self, case: &Path, timelimit: f64, args: &GenerateArgs) -> Result<CaseCheck> {
+        let mut check = CaseCheck::default();
+        if let Some((dir, runstep)) = &self.validator {
+            if let Some(message) = validate_case(dir.path(), runstep, case, timelimit)? {
+                remove_file(case)?;
+                check.failure = Some(format!("{case:?}: INVALID ({message})"));
+                check.invalid = Some(message);
+                return Ok(check);
+            }
+        }
+
+        // outdir 以下の構成は answer_dir でも保つ
+        if let Some((dir, runstep)) = &self.solver {
+            let subdir = case
+                .parent()
+                .and_then(|parent| parent.strip_prefix(&args.outdir).ok())
+                .unwrap_or(Path::new(""));
+            let answer_dir = args.answer_dir.join(subdir);
+            create_dir_all(&answer_dir)?;
+
+            let (status, answer) = solve::solve(dir, case, &answer_dir, runstep, args.timelimit)?;
+            info!("[SOLVE] {answer:?}, status = {status:?}");
+            if !status.success() {
+                let log_path = solve::stderr_log_path(&answer_dir, case);
+                check.failure = Some(if log_path.exists() {
+                    format!("{answer:?}: {status} (stderr: {log_path:?})")
+                } else {
+                    format!("{answer:?}: {status}")
+                });
+            }
+            check.answer = Some((status, answer));
+        }
+        Ok(check)
+    }
 }
 
 /// 生成されたテストケースへのパスを返す
+/// skip_existing の場合，既に存在するケースは生成しない（全て存在すればコンパイルもしない）
+/// 生成に失敗したケースの出力は削除され，keep_going でなければそこで打ち切る
+/// 生成した各ケースはその場で checks により検査する
 fn generate(
     target: &GenFileInfo,
-    outdir: &Path,
-    count: u32,
-    seed: u32,
-    timelimit: f64,
     langs: &Vec<Box<dyn Language>>,
+    args: &GenerateArgs,
+    checks: &CaseChecks,
     bar: &ProgressBar,
-) -> Result<Vec<(ExecuteStatus, PathBuf)>> {
+    deadline: Option<Instant>,
+    over_budget: &mut Vec<PathBuf>,
+) -> Result<Vec<(ExecuteStatus, PathBuf, u32, CaseCheck)>> {
+    let outdir = &target.outdir(args);
+    let exhausted = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    let mut pending = Vec::new();
+    for (seed, output_path) in output_paths(target, args)? {
+        if args.skip_existing && output_path.exists() {
+            bar.inc(1);
+            continue;
+        }
+        pending.push((seed, output_path));
+    }
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+    if exhausted() {
+        over_budget.extend(pending.into_iter().map(|(_, path)| path));
+        return Ok(Vec::new());
+    }
+
     // compile
+    bar.set_message(format!("{} (compiling)", target.name));
     let dir = TempDir::new()?;
-    let runstep = compile_and_get_runstep(&dir, &target.path, &langs)?;
+    let runstep = with_envs(
+        compile_and_get_runstep(&dir, &target.path, langs)?,
+        &args.env,
+    );
+
+    let post_step = match args.post_cmd {
+        Some(ref post_cmd) => Some(post_cmd_step(post_cmd)?),
+        None => None,
+    };
 
     // generate
-    let count = target.count.unwrap_or(count);
+    create_dir_all(outdir.join("logs"))?;
     let mut generated_cases = Vec::new();
-    for i in 0..count {
-        let output_name = format!("{}_{:03}.in", &target.name, i);
-        let output_path = outdir.join(output_name);
-        let output = File::create(&output_path).unwrap();
+    // --retry で他のケースの seed を使わないよう，予定している seed を記録しておく
+    let mut used_seeds: HashSet<u32> = pending.iter().map(|(seed, _)| *seed).collect();
+    // --multi で次に割り当てる番号
+    let mut next_index = 0;
+    let mut pending = pending.into_iter();
+    while let Some((planned_seed, output_path)) = pending.next() {
+        if exhausted() {
+            over_budget.push(output_path);
+            over_budget.extend(pending.map(|(_, path)| path));
+            break;
+        }
+        bar.set_message(format!("{} seed = {planned_seed}", target.name));
 
-        let status = runstep
-            .execute(
-                &dir,
-                vec![(seed + i as u32).to_string()],
-                Stdio::null(),
-                output,
-                Stdio::null(),
-                Duration::from_secs_f64(timelimit),
-            )
-            .with_context(|| {
-                format!(
-                    "failed to generate {:?} at seed = {:?}",
-                    target.path,
-                    seed + i
+        // --multi では，この実行で最初に割り当てるケースの名前でログを保存する
+        let first_path = if args.multi {
+            case_path(target, args, next_index, planned_seed)?
+        } else {
+            output_path.clone()
+        };
+        // 中断されても途中までの .in が残らないよう，一時ファイルに書き込んでから置き換える
+        let output = tempfile::Builder::new()
+            .prefix(".")
+            .suffix(".tmp")
+            .tempfile_in(first_path.parent().unwrap_or(outdir))?;
+        let log_path = stderr_log_path(&first_path);
+
+        // ジェネレータが seed を拒否した（異常終了した）場合は未使用の seed で再試行する
+        let mut seed = planned_seed;
+        let mut retries = 0;
+        let (status, multi_dir) = loop {
+            output.as_file().set_len(0)?;
+            let (runstep, mut seed_args, stdin) =
+                pass_seed(&runstep, &args.seed_via, seed, &target.args, dir.path())?;
+            // --multi ではジェネレータは最後の引数のディレクトリにケースを書き込む
+            let multi_dir = if args.multi {
+                let multi_dir = tempfile::Builder::new()
+                    .prefix(".")
+                    .tempdir_in(first_path.parent().unwrap_or(outdir))?;
+                seed_args.push(
+                    multi_dir
+                        .path()
+                        .canonicalize()?
+                        .to_string_lossy()
+                        .to_string(),
+                );
+                Some(multi_dir)
+            } else {
+                None
+            };
+            let status = runstep
+                .execute(
+                    &dir,
+                    seed_args,
+                    stdin,
+                    output.reopen()?,
+                    File::create(&log_path)?,
+                    Duration::from_secs_f64(target.timelimit(args)),
+                )
+                .with_context(|| {
+                    format!("failed to generate {:?} at seed = {:?}", target.path, seed)
+                })?;
+
+            if status != ExecuteStatus::Fail || retries >= args.retry {
+                break (status, multi_dir);
+            }
+            retries += 1;
+            seed = next_unused_seed(seed, &mut used_seeds);
+            bar.set_message(format!("{} seed = {seed} (retry {retries})", target.name));
+        };
+        if status.success() && seed != planned_seed {
+            bar.suspend(|| {
+                println!(
+                    "[RETRY] {first_path:?}: generated with seed = {seed} instead of {planned_seed}"
                 )
-            })?;
+            });
+        }
 
-        generated_cases.push((status, output_path.to_path_buf()));
+        // (一時ファイル, 出力先) の組
+        let outputs = match multi_dir {
+            Some(ref multi_dir) if status.success() => {
+                let mut files = find_files(multi_dir.path(), false)?;
+                files.sort();
+                if files.is_empty() {
+                    bar.suspend(|| {
+                        println!("[MULTI] {:?} wrote no case at seed = {seed}", target.path)
+                    });
+                }
+                let mut outputs = Vec::new();
+                for file in files {
+                    let output_path = case_path(target, args, next_index, planned_seed)?;
+                    next_index += 1;
+                    outputs.push((TempPath::from_path(file), output_path));
+                }
+                outputs
+            }
+            Some(_) => vec![(output.into_temp_path(), first_path.clone())],
+            None => vec![(output.into_temp_path(), output_path)],
+        };
+
+        let mut success = status.success();
+        for (output, output_path) in outputs {
+            let status = save_case(
+                output,
+                &output_path,
+                status,
+                post_step.as_ref(),
+                &log_path,
+                args,
+            )?;
+            let check = if status.success() {
+                checks.run(&output_path, target.timelimit(args), args)?
+            } else {
+                CaseCheck::default()
+            };
+            success &= status.success() && check.passed();
+            generated_cases.push((status, output_path, seed, check));
+        }
+
+        // 空のログは残さない
+        if log_path.metadata()?.len() == 0 {
+            remove_file(&log_path)?;
+        }
+
+        bar.inc(1);
+        if !success && !args.keep_going {
+            break;
+        }
     }
 
-    bar.inc(1);
+    // ログが 1 つもなければ logs ディレクトリも削除する（空でなければ削除に失敗する）
+    let _ = remove_dir(outdir.join("logs"));
+
     Ok(generated_cases)
 }
 
-pub(super) fn root(args: GenerateArgs) -> Result<()> {
+/// manual_dir 以下の手書きのケース（.txt, .in）のコピー先 {name}_000.in を決める
+/// (コピー先, コピー元) を返す．コピー先が重複する場合はエラーとする
+fn manual_cases(manual_dir: &Path, outdir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    ensure!(
+        manual_dir.is_dir(),
+        "manual directory {manual_dir:?} not found"
+    );
+
+    let mut cases = Vec::new();
+    let mut owners: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for file in find_files(manual_dir, true)? {
+        if file
+            .extension()
+            .is_none_or(|ext| ext != "txt" && ext != "in")
+        {
+            continue;
+        }
+
+        let name = file.file_stem().unwrap().to_string_lossy().to_string();
+        let output_path = outdir.join(format!("{name}_000.in"));
+        if let Some(other) = owners.insert(output_path.clone(), file.clone()) {
+            bail!(
+                "{output_path:?} would be copied twice (from {other:?} and {file:?}). Rename the manual case"
+            );
+        }
+        cases.push((output_path, file));
+    }
+    Ok(cases)
+}
+
+/// outdir 直下の .in, .in.gz ファイルと logs ディレクトリを削除し，削除したケースの数を返す
+fn remove_stale_cases(outdir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for file in find_files(outdir, false)? {
+        if is_input(&file) {
+            remove_file(&file)?;
+            removed += 1;
+        }
+    }
+
+    let logs = outdir.join("logs");
+    if logs.is_dir() {
+        remove_dir_all(&logs)?;
+    }
+    Ok(removed)
+}
+
+/// 生成されたケースが空または max_size (MiB) より大きい場合にその旨を返す
+/// 空のケースはジェネレータのクラッシュや seed の無視を疑う
+fn check_size(case: &Path, max_size: f64) -> Result<Option<String>> {
+    let size = input_size(case)?;
+    if size == 0 {
+        Ok(Some(format!("{case:?} is empty")))
+    } else if size as f64 > max_size * 1024.0 * 1024.0 {
+        Ok(Some(format!(
+            "{case:?} is {:.1} MiB (> {max_size} MiB)",
+            size as f64 / 1024.0 / 1024.0
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+/// 生成されたケースを validator で検証する
+/// 不正な場合は validator の標準エラー出力の先頭行を返す
+fn validate_case(
+    dir: &Path,
+    runstep: &CommandStep,
+    case: &Path,
+    timelimit: f64,
+) -> Result<Option<String>> {
+    let message_path = dir.join("validator.err");
+    let inflate_dir = TempDir::new()?;
+    let status = runstep.execute(
+        dir,
+        Vec::new(),
+        File::open(inflate_input(case, inflate_dir.path())?)?,
+        Stdio::null(),
+        File::create(&message_path)?,
+        Duration::from_secs_f64(timelimit),
+    )?;
+
+    if status.success() {
+        Ok(None)
+    } else {
+        let message = read_to_string(&message_path)?;
+        let first_line = message.lines().next().unwrap_or_default();
+        Ok(Some(format!("{status}: {first_line}")))
+    }
+}
+
+pub(super) fn root(mut args: GenerateArgs) -> Result<()> {
     info!("{:#?}", args);
 
+    if let Some(ref seed_file) = args.seed_file {
+        args.seeds = read_seed_file(seed_file)?;
+    }
+    if let Some(ref count_map) = args.count_map {
+        let mut specs = read_count_map(count_map)?;
+        args.count.append(&mut specs);
+    }
+
     let generators = {
-        let mut generators = Vec::new();
-        for base in args.generators {
-            for file in find_files(&base, args.recursive)? {
+        let mut generators = match args.recipe {
+            Some(ref recipe) => recipe::load_recipe(recipe)?,
+            None => Vec::new(),
+        };
+        for base in &args.generators {
+            for file in find_files(base, args.recursive)? {
                 generators.push(GenFileInfo::new(&file)?);
             }
         }
+        if let Some(ref subtask) = args.subtask {
+            let subtask = subtask_dir(subtask)?;
+            for target in generators.iter_mut() {
+                target.subtask.get_or_insert_with(|| subtask.clone());
+            }
+        }
         generators
     };
-    if generators.len() == 0 {
+    if generators.is_empty() && args.manual.is_none() {
         println!("no generator found!");
         return Ok(());
     }
     info!("generators = {generators:#?}");
 
+    // 打ち間違いに気付けるよう，どのジェネレータにも一致しない指定はエラーとする
+    for spec in &args.count {
+        if let CountSpec::For(name, _) = spec {
+            ensure!(
+                generators
+                    .iter()
+                    .any(|target| matches_generator(name, &target.path)),
+                "no generator matches {name:?} in --count"
+            );
+        }
+    }
+
+    ensure!(
+        args.name_format.ends_with(".in"),
+        "name format {:?} must end with .in",
+        args.name_format
+    );
+    format_name(&args.name_format, "", 0, 0, &[])?;
+    ensure!(
+        !args.append || is_numbered(&args.name_format),
+        "name format {:?} must contain {{index}} or {{seed}} with --append",
+        args.name_format
+    );
+
+    let manual = match args.manual {
+        Some(ref manual_dir) if !args.stress => manual_cases(manual_dir, &args.outdir)?,
+        _ => Vec::new(),
+    };
+    if !args.stress {
+        check_name_collisions(&generators, &manual, &args)?;
+    }
+
+    if args.dry_run {
+        return print_plan(&generators, &args);
+    }
+
     let langs = make_languages(&args.language)?;
 
+    if args.stress {
+        return stress::stress(&generators, &langs, &args);
+    }
+
     if !args.outdir.exists() {
         create_dir_all(&args.outdir)?;
+    } else if args.fresh {
+        // 小課題のディレクトリは今回の出力先のみ掃除する
+        let mut dirs = vec![args.outdir.clone()];
+        dirs.extend(generators.iter().map(|target| target.outdir(&args)));
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs.iter().filter(|dir| dir.is_dir()) {
+            let removed = remove_stale_cases(dir)?;
+            println!("{removed} stale case(s) removed from {dir:?}");
+        }
     }
 
-    #[derive(Tabled)]
-    struct Result {
-        status: String,
-        generated_case: String,
-        from: String,
-    }
-    let mut results = Vec::new();
+    let checks = CaseChecks {
+        validator: match args.validator {
+            Some(ref validator) => {
+                ensure!(validator.exists(), "validator {validator:?} not found");
+                let dir = TempDir::new()?;
+                let runstep = compile_and_get_runstep(&dir, validator, &langs)?;
+                Some((dir, runstep))
+            }
+            None => None,
+        },
+        solver: match args.solver {
+            Some(ref solver) => {
+                ensure!(solver.exists(), "solver {solver:?} not found");
+                let dir = TempDir::new()?;
+                let runstep = compile_and_get_runstep(&dir, solver, &langs)?;
+                Some((dir, runstep))
+            }
+            None => None,
+        },
+    };
 
     let count = generators
         .iter()
-        .fold(0, |sum, x| sum + x.count.unwrap_or(args.count));
+        .fold(0, |sum, x| sum + x.seeds(&args).len());
     let bar = ProgressBar::new(count as u64);
-    bar.set_style(ProgressStyle::default_bar().template("[Generate] {bar} {pos:>4}/{len:4}")?);
+    bar.set_style(
+        ProgressStyle::default_bar().template("[Generate] {bar} {pos:>4}/{len:4} {msg}")?,
+    );
+
+    // (status, 生成されたケース, 生成元)
+    let mut cases = Vec::new();
+    let mut failures = Vec::new();
+    let mut skipped = 0;
+    // manifest.json に記録する来歴
+    let mut entries = Vec::new();
+    // --budget を使い切ったために生成しなかったケース
+    let deadline = args.budget.map(|budget| Instant::now() + budget);
+    let mut over_budget = Vec::new();
     for target in generators {
+        if args.skip_existing {
+            skipped += output_paths(&target, &args)?
+                .iter()
+                .filter(|(_, path)| path.exists())
+                .count();
+        }
         match generate(
             &target,
-            &args.outdir,
-            args.count,
-            args.seed,
-            args.timelimit,
             &langs,
+            &args,
+            &checks,
             &bar,
+            deadline,
+            &mut over_budget,
         ) {
-            Ok(cases) => {
-                for (status, case) in cases {
+            Ok(generated_cases) => {
+                let source_hash = manifest::source_hash(&target.path)?;
+                for (status, case, seed, check) in generated_cases {
                     info!("[GENERATE] {case:?}, status = {status:?}");
-
-                    results.push(Result {
-                        status: status.to_string(),
-                        generated_case: format!("{:?}", case),
-                        from: format!("{:?}", target.path),
-                    });
+                    if !status.success() {
+                        failures.push(format!("{case:?}: {status}"));
+                    }
+                    failures.extend(check.failure.clone());
+                    entries.push(manifest::Entry::new(
+                        &args.outdir,
+                        &case,
+                        &target.path,
+                        &source_hash,
+                        Some((seed, args.seed_via.to_string())),
+                        &target.args,
+                    ));
+                    cases.push((status, case, target.path.clone(), check));
                 }
             }
             Err(err) => {
-                warn!("[IGNORE] {:?}, reason = {:?}", target, err);
+                warn!("[FAIL] {:?}, reason = {:?}", target, err);
+                failures.push(format!("{:?}: {err}", target.path));
             }
         }
+
+        if !failures.is_empty() && !args.keep_going {
+            break;
+        }
+    }
+    bar.finish_with_message("");
+
+    if failures.is_empty() || args.keep_going {
+        for (case, from) in manual {
+            copy(&from, &case)?;
+            info!("[MANUAL] {case:?}");
+            let check = checks.run(&case, args.timelimit, &args)?;
+            failures.extend(check.failure.clone());
+            entries.push(manifest::Entry::new(
+                &args.outdir,
+                &case,
+                &from,
+                &manifest::source_hash(&from)?,
+                None,
+                &[],
+            ));
+            cases.push((ExecuteStatus::Success, case, from, check));
+        }
+    }
+
+    #[derive(Tabled)]
+    struct Result {
+        status: String,
+        generated_case: String,
+        from: String,
+    }
+    #[derive(Tabled)]
+    struct Answer {
+        status: String,
+        input: String,
+        generated_answer: String,
+    }
+    let mut results = Vec::new();
+    let mut answers = Vec::new();
+    let mut generated = Vec::new();
+    for (status, case, from, check) in cases {
+        if let Some((status, ref answer)) = check.answer {
+            answers.push(Answer {
+                status: status.to_string(),
+                input: format!("{:?}", case),
+                generated_answer: format!("{:?}", answer),
+            });
+        }
+        let status = match check.invalid {
+            Some(message) => format!("INVALID\n{message}"),
+            None if status.success() => {
+                generated.push(case.clone());
+                status.to_string()
+            }
+            None => {
+                // 失敗した場合はジェネレータの標準エラー出力の保存先を表示する
+                let log_path = stderr_log_path(&case);
+                if log_path.exists() {
+                    format!("{status}\n{log_path:?}")
+                } else {
+                    status.to_string()
+                }
+            }
+        };
+
+        results.push(Result {
+            status,
+            generated_case: format!("{:?}", case),
+            from: format!("{:?}", from),
+        });
     }
-    bar.finish();
 
     println!("{}", Table::new(results));
 
+    // 生成に成功し，不正でなかったケースのみ記録する
+    entries.retain(|entry| {
+        generated
+            .iter()
+            .any(|case| case.strip_prefix(&args.outdir).unwrap_or(case) == Path::new(&entry.case))
+    });
+    manifest::update_manifest(&args.outdir, entries)?;
+
+    // 生成に成功したケースの想定解による答え
+    if checks.solver.is_some() {
+        println!("{}", Table::new(answers));
+    }
+
+    for case in &generated {
+        if let Some(message) = check_size(case, args.max_size)? {
+            println!("[SIZE] {message}");
+            if args.strict_size {
+                failures.push(message);
+            }
+        }
+    }
+    if skipped > 0 {
+        println!("{skipped} existing case(s) skipped");
+    }
+    if !over_budget.is_empty() {
+        println!(
+            "[BUDGET] {} case(s) skipped because the budget was exhausted:",
+            over_budget.len()
+        );
+        for case in &over_budget {
+            println!("  {case:?}");
+        }
+    }
+
+    // seed の扱いの誤り等で同一のケースが生成されていないか確認する
+    let duplicates = find_duplicates(&generated)?;
+    for (original, duplicate) in &duplicates {
+        println!("[DUPLICATE] {duplicate:?} is identical to {original:?}");
+    }
+    ensure!(
+        !args.no_duplicates || duplicates.is_empty(),
+        "{} duplicate case(s) generated",
+        duplicates.len()
+    );
+
+    for failure in &failures {
+        println!("[FAIL] {failure}");
+    }
+    ensure!(
+        failures.is_empty(),
+        "{} generation(s) failed",
+        failures.len()
+    );
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: GenerateArgs,
+    }
+
+    fn parse_args(args: &[&str]) -> GenerateArgs {
+        Cli::parse_from([&["kuroe"], args].concat()).args
+    }
+
+    #[test]
+    fn test_save_case() {
+        let dir = TempDir::new().unwrap();
+        let args = parse_args(&["random.cpp"]);
+        let output_path = dir.path().join("random_000.in");
+        let log_path = dir.path().join("random_000.err");
+
+        let temp = dir.path().join("c0.in");
+        write(&temp, "1\n").unwrap();
+        let status = save_case(
+            TempPath::from_path(&temp),
+            &output_path,
+            ExecuteStatus::Success,
+            None,
+            &log_path,
+            &args,
+        )
+        .unwrap();
+        assert!(status.success());
+        assert!(!temp.exists());
+        assert_eq!(read_to_string(&output_path).unwrap(), "1\n");
+
+        // 失敗した場合は以前の生成結果も削除する
+        write(&temp, "2\n").unwrap();
+        let status = save_case(
+            TempPath::from_path(&temp),
+            &output_path,
+            ExecuteStatus::Fail,
+            None,
+            &log_path,
+            &args,
+        )
+        .unwrap();
+        assert_eq!(status, ExecuteStatus::Fail);
+        assert!(!temp.exists());
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    fn test_output_paths_append() {
+        let dir = TempDir::new().unwrap();
+        let outdir = dir.path().to_string_lossy().to_string();
+        let target = GenFileInfo::new(Path::new("random.cpp")).unwrap();
+        for i in 0..2 {
+            File::create(dir.path().join(format!("random_00{i}.in"))).unwrap();
+        }
+
+        let args = parse_args(&["random.cpp", "-o", &outdir, "-n", "2", "-s", "10"]);
+        assert_eq!(
+            output_paths(&target, &args).unwrap(),
+            vec![
+                (10, dir.path().join("random_000.in")),
+                (11, dir.path().join("random_001.in")),
+            ]
+        );
+
+        let args = parse_args(&[
+            "random.cpp",
+            "-o",
+            &outdir,
+            "-n",
+            "2",
+            "-s",
+            "10",
+            "--append",
+        ]);
+        assert_eq!(
+            output_paths(&target, &args).unwrap(),
+            vec![
+                (12, dir.path().join("random_002.in")),
+                (13, dir.path().join("random_003.in")),
+            ]
+        );
+
+        // 明示された seed はずらさない
+        let args = parse_args(&[
+            "random.cpp",
+            "-o",
+            &outdir,
+            "--seeds",
+            "3,4294967295",
+            "--append",
+        ]);
+        assert_eq!(
+            output_paths(&target, &args).unwrap(),
+            vec![
+                (3, dir.path().join("random_002.in")),
+                (u32::MAX, dir.path().join("random_003.in")),
+            ]
+        );
+
+        // 番号が飛んでいる場合は上書きせずにエラーとする
+        File::create(dir.path().join("random_003.in")).unwrap();
+        assert!(output_paths(&target, &args).is_err());
+
+        // 番号を含まない名前では（既存のケースがあっても）数え続けずにエラーとする
+        File::create(dir.path().join("random.in")).unwrap();
+        let args = parse_args(&[
+            "random.cpp",
+            "-o",
+            &outdir,
+            "--name-format",
+            "{name}.in",
+            "--append",
+        ]);
+        assert!(output_paths(&target, &args).is_err());
+        assert!(is_numbered("{name}_{index:03}.in"));
+        assert!(is_numbered("{name}_{seed}.in"));
+        assert!(!is_numbered("{name}{params}.in"));
+    }
+
+    #[test]
+    fn test_check_name_collisions() {
+        let args = parse_args(&["gen", "-n", "2"]);
+        let small = GenFileInfo::new(Path::new("small/max.cpp")).unwrap();
+        let large = GenFileInfo::new(Path::new("large/max.cpp")).unwrap();
+        let random = GenFileInfo::new(Path::new("large/random.cpp")).unwrap();
+        assert!(check_name_collisions(&[small.clone(), random.clone()], &[], &args).is_ok());
+        assert!(check_name_collisions(&[small.clone(), large], &[], &args).is_err());
+
+        let mut renamed = small.clone();
+        renamed.name = "max_small".to_string();
+        assert!(check_name_collisions(&[small, renamed], &[], &args).is_ok());
+
+        // {index} を含まない名前では同じジェネレータの出力同士が衝突する
+        let args = parse_args(&["gen", "-n", "2", "--name-format", "{name}.in"]);
+        assert!(check_name_collisions(&[random], &[], &args).is_err());
+    }
 
     #[test]
     fn test_genfileinfo() {
@@ -218,5 +1512,316 @@ mod tests {
 
         let info = GenFileInfo::new(Path::new("0.ext"));
         assert!(info.is_err());
+
+        let info = GenFileInfo::new(Path::new("max.3.tl120.cpp")).unwrap();
+        assert_eq!(info.name, String::from("max"));
+        assert_eq!(info.count, Some(3));
+        assert_eq!(info.timelimit, Some(120.0));
+
+        let info = GenFileInfo::new(Path::new("max.tl1.5.cpp")).unwrap();
+        assert_eq!(info.name, String::from("max"));
+        assert_eq!(info.count, None);
+        assert_eq!(info.timelimit, Some(1.5));
+        assert_eq!(
+            GenFileInfo::new(Path::new("test.5.ext")).unwrap().timelimit,
+            None
+        );
+
+        assert!(GenFileInfo::new(Path::new("max.tl0.cpp")).is_err());
+    }
+
+    #[test]
+    fn test_parse_seed_via() {
+        assert_eq!(parse_seed_via("argv").unwrap(), SeedVia::Argv);
+        assert_eq!(parse_seed_via("stdin").unwrap(), SeedVia::Stdin);
+        assert_eq!(
+            parse_seed_via("env").unwrap(),
+            SeedVia::Env("SEED".to_string())
+        );
+        assert_eq!(
+            parse_seed_via("env:RANDOM_SEED").unwrap(),
+            SeedVia::Env("RANDOM_SEED".to_string())
+        );
+        assert_eq!(parse_seed_via("testlib").unwrap(), SeedVia::Testlib);
+        assert!(parse_seed_via("env:").is_err());
+        assert!(parse_seed_via("file").is_err());
+    }
+
+    #[test]
+    fn test_pass_seed() {
+        let dir = TempDir::new().unwrap();
+        let runstep = CommandStep::new(
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                "read stdin; printf '%s|%s|%s' \"$stdin\" \"$SEED\" \"$*\"".to_string(),
+                "sh".to_string(),
+            ],
+        );
+        let output_path = dir.path().join("output.txt");
+        let run = |seed_via: SeedVia| {
+            let (runstep, args, stdin) =
+                pass_seed(&runstep, &seed_via, 7, &["x".to_string()], dir.path()).unwrap();
+            runstep
+                .execute(
+                    dir.path(),
+                    args,
+                    stdin,
+                    File::create(&output_path).unwrap(),
+                    Stdio::null(),
+                    Duration::from_secs(1),
+                )
+                .unwrap();
+            read_to_string(&output_path).unwrap()
+        };
+
+        assert_eq!(run(SeedVia::Argv), "||7 x");
+        assert_eq!(run(SeedVia::Stdin), "7||x");
+        assert_eq!(run(SeedVia::Env("SEED".to_string())), "|7|x");
+        assert_eq!(run(SeedVia::Testlib), "||x --testcase-index=7");
+    }
+
+    #[test]
+    fn test_subtask_dir() {
+        assert_eq!(subtask_dir("1").unwrap(), "subtask1");
+        assert_eq!(subtask_dir("sample").unwrap(), "sample");
+        assert!(subtask_dir("").is_err());
+        assert!(subtask_dir("a/b").is_err());
+        assert!(subtask_dir("..").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("300").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("300s").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn test_parse_env() {
+        assert_eq!(
+            parse_env("MODE=tree").unwrap(),
+            ("MODE".to_string(), "tree".to_string())
+        );
+        assert_eq!(
+            parse_env("EXPR=a=b").unwrap(),
+            ("EXPR".to_string(), "a=b".to_string())
+        );
+        assert_eq!(
+            parse_env("EMPTY=").unwrap(),
+            ("EMPTY".to_string(), String::new())
+        );
+        assert!(parse_env("MODE").is_err());
+        assert!(parse_env("=tree").is_err());
+    }
+
+    #[test]
+    fn test_post_cmd_step() {
+        let dir = TempDir::new().unwrap();
+        let case = dir.path().join("a b.in");
+        write(&case, "1 2  \n3 \n").unwrap();
+
+        let step = post_cmd_step("sed -i 's/ *$//' %(file)").unwrap();
+        let status = step
+            .execute(
+                dir.path(),
+                vec![case.to_string_lossy().to_string()],
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(read_to_string(&case).unwrap(), "1 2\n3\n");
+
+        assert!(post_cmd_step("normalize").is_err());
+    }
+
+    #[test]
+    fn test_next_unused_seed() {
+        let mut used = HashSet::from([0, 1, 2, 4]);
+        assert_eq!(next_unused_seed(0, &mut used), 3);
+        assert_eq!(next_unused_seed(3, &mut used), 5);
+        assert_eq!(next_unused_seed(1, &mut used), 6);
+        assert_eq!(next_unused_seed(u32::MAX, &mut used), 7);
+    }
+
+    #[test]
+    fn test_parse_count_spec() {
+        assert_eq!(parse_count_spec("5").unwrap(), CountSpec::All(5));
+        assert_eq!(
+            parse_count_spec("gen_random.cpp=50").unwrap(),
+            CountSpec::For("gen_random.cpp".to_string(), 50)
+        );
+        assert!(parse_count_spec("0").is_err());
+        assert!(parse_count_spec("gen.cpp=0").is_err());
+        assert!(parse_count_spec("=3").is_err());
+        assert!(parse_count_spec("gen.cpp").is_err());
+    }
+
+    #[test]
+    fn test_matches_generator() {
+        let path = Path::new("gen/random.cpp");
+        assert!(matches_generator("random.cpp", path));
+        assert!(matches_generator("gen/random.cpp", path));
+        assert!(!matches_generator("random", path));
+        assert!(!matches_generator("max.cpp", path));
+    }
+
+    #[test]
+    fn test_read_count_map() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("counts.txt");
+        write(&path, "# counts\nrandom.cpp=50\nmax.cpp=3, 2\n").unwrap();
+        assert_eq!(
+            read_count_map(&path).unwrap(),
+            vec![
+                CountSpec::For("random.cpp".to_string(), 50),
+                CountSpec::For("max.cpp".to_string(), 3),
+                CountSpec::All(2),
+            ]
+        );
+
+        write(&path, "random.cpp=x\n").unwrap();
+        assert!(read_count_map(&path).is_err());
+    }
+
+    #[test]
+    fn test_read_seed_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("seeds.txt");
+        std::fs::write(&path, "# counterexamples\n3, 17\n42 # found by stress\n").unwrap();
+        assert_eq!(read_seed_file(&path).unwrap(), vec![3, 17, 42]);
+
+        std::fs::write(&path, "3 x\n").unwrap();
+        assert!(read_seed_file(&path).is_err());
+
+        std::fs::write(&path, "# empty\n").unwrap();
+        assert!(read_seed_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_format_name() {
+        let params = vec!["--n".to_string(), "10".to_string()];
+        assert_eq!(
+            format_name("{name}_{index:03}.in", "random", 7, 107, &params).unwrap(),
+            "random_007.in"
+        );
+        assert_eq!(
+            format_name("{name}_{seed}.in", "random", 7, 107, &params).unwrap(),
+            "random_107.in"
+        );
+        assert_eq!(
+            format_name("{name}{params}_{index}.in", "random", 7, 107, &params).unwrap(),
+            "random--n_10_7.in"
+        );
+        assert!(format_name("{unknown}.in", "random", 7, 107, &params).is_err());
+    }
+
+    #[test]
+    fn test_remove_stale_cases() {
+        let dir = TempDir::new().unwrap();
+        File::create(dir.path().join("old_000.in")).unwrap();
+        File::create(dir.path().join("note.txt")).unwrap();
+        create_dir_all(dir.path().join("logs")).unwrap();
+        File::create(dir.path().join("logs").join("old_000.err")).unwrap();
+        create_dir_all(dir.path().join("sub")).unwrap();
+        File::create(dir.path().join("sub").join("keep_000.in")).unwrap();
+
+        assert_eq!(remove_stale_cases(dir.path()).unwrap(), 1);
+        assert!(!dir.path().join("old_000.in").exists());
+        assert!(!dir.path().join("logs").exists());
+        assert!(dir.path().join("note.txt").exists());
+        assert!(dir.path().join("sub").join("keep_000.in").exists());
+    }
+
+    #[test]
+    fn test_check_size() {
+        let dir = TempDir::new().unwrap();
+        let case = dir.path().join("case.in");
+
+        File::create(&case).unwrap();
+        assert!(check_size(&case, 1.0).unwrap().unwrap().contains("empty"));
+
+        write(&case, "1\n").unwrap();
+        assert_eq!(check_size(&case, 1.0).unwrap(), None);
+
+        write(&case, vec![b'0'; 2 * 1024 * 1024]).unwrap();
+        assert!(check_size(&case, 1.0).unwrap().unwrap().contains("2.0 MiB"));
+    }
+
+    #[test]
+    fn test_stderr_log_path() {
+        assert_eq!(
+            stderr_log_path(Path::new("testcases/input/random_000.in")),
+            PathBuf::from("testcases/input/logs/random_000.err")
+        );
+    }
+
+    #[test]
+    fn test_validate_case() {
+        let dir = TempDir::new().unwrap();
+        let runstep = CommandStep::new(
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                "grep -q 1 || { echo 'must contain 1' >&2; exit 1; }".to_string(),
+            ],
+        );
+
+        let valid = dir.path().join("valid.in");
+        std::fs::write(&valid, "1\n").unwrap();
+        assert_eq!(
+            validate_case(dir.path(), &runstep, &valid, 10.0).unwrap(),
+            None
+        );
+
+        let invalid = dir.path().join("invalid.in");
+        std::fs::write(&invalid, "2\n").unwrap();
+        assert_eq!(
+            validate_case(dir.path(), &runstep, &invalid, 10.0).unwrap(),
+            Some("FAIL: must contain 1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_manual_cases() {
+        let dir = TempDir::new().unwrap();
+        let manual_dir = dir.path().join("manual");
+        let outdir = dir.path().join("input");
+        create_dir_all(manual_dir.join("sub")).unwrap();
+        create_dir_all(&outdir).unwrap();
+        std::fs::write(manual_dir.join("edge.txt"), "1 1\n").unwrap();
+        std::fs::write(manual_dir.join("sub/max.in"), "9 9\n").unwrap();
+        std::fs::write(manual_dir.join("README.md"), "memo").unwrap();
+
+        let mut cases = manual_cases(&manual_dir, &outdir).unwrap();
+        cases.sort();
+        assert_eq!(
+            cases,
+            vec![
+                (outdir.join("edge_000.in"), manual_dir.join("edge.txt")),
+                (outdir.join("max_000.in"), manual_dir.join("sub/max.in")),
+            ]
+        );
+
+        // ジェネレータの出力と衝突する手書きのケース
+        let args = parse_args(&["gen", "-o", &outdir.to_string_lossy()]);
+        let max = GenFileInfo::new(Path::new("gen/max.cpp")).unwrap();
+        let random = GenFileInfo::new(Path::new("gen/random.cpp")).unwrap();
+        assert!(check_name_collisions(&[random], &cases, &args).is_ok());
+        assert!(check_name_collisions(&[max], &cases, &args).is_err());
+
+        // 別のディレクトリの同名の手書きのケース
+        std::fs::write(manual_dir.join("sub/edge.in"), "2 2\n").unwrap();
+        assert!(manual_cases(&manual_dir, &outdir).is_err());
+
+        assert!(manual_cases(&dir.path().join("missing"), &outdir).is_err());
     }
 }
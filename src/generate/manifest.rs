@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{read, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+/// 生成されたケースの来歴を記録する outdir/manifest.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    cases: Vec<Entry>,
+}
+
+/// manifest.json の 1 ケース分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct Entry {
+    /// outdir からの相対パス
+    pub(super) case: String,
+    /// ジェネレータ（手書きのケースはコピー元）のパス
+    pub(super) generator: String,
+    /// ジェネレータのソースの SHA-256
+    pub(super) source_hash: String,
+    /// 手書きのケースは None
+    pub(super) seed: Option<u32>,
+    pub(super) seed_via: Option<String>,
+    pub(super) args: Vec<String>,
+    pub(super) kuroe_version: String,
+}
+
+impl Entry {
+    pub(super) fn new(
+        outdir: &Path,
+        case: &Path,
+        generator: &Path,
+        source_hash: &str,
+        seed: Option<(u32, String)>,
+        args: &[String],
+    ) -> Self {
+        let case = case.strip_prefix(outdir).unwrap_or(case);
+        let (seed, seed_via) = match seed {
+            Some((seed, seed_via)) => (Some(seed), Some(seed_via)),
+            None => (None, None),
+        };
+        Entry {
+            case: case.to_string_lossy().replace('\\', "/"),
+            generator: generator.to_string_lossy().to_string(),
+            source_hash: source_hash.to_string(),
+            seed,
+            seed_via,
+            args: args.to_vec(),
+            kuroe_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// path の内容の SHA-256 を 16 進数で返す
+pub(crate) fn source_hash(path: &Path) -> Result<String> {
+    let content = read(path).with_context(|| format!("failed to read {path:?}"))?;
+    let digest = Sha256::digest(&content);
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// outdir/manifest.json に entries を記録し，そのパスを返す
+/// 既存の記録は，今回生成したケースと既に存在しないケースを除いて引き継ぐ
+pub(super) fn update_manifest(outdir: &Path, entries: Vec<Entry>) -> Result<PathBuf> {
+    let path = outdir.join("manifest.json");
+    let mut manifest = match read_to_string(&path) {
+        Ok(content) => serde_json::from_str::<Manifest>(&content)
+            .with_context(|| format!("failed to parse {path:?}"))?,
+        Err(_) => Manifest::default(),
+    };
+
+    manifest.cases.retain(|old| {
+        outdir.join(&old.case).exists() && entries.iter().all(|entry| entry.case != old.case)
+    });
+    manifest.cases.extend(entries);
+    manifest.cases.sort_by(|a, b| a.case.cmp(&b.case));
+
+    write(&path, serde_json::to_string_pretty(&manifest)? + "\n")?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_source_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gen.py");
+        write(&path, "abc").unwrap();
+        assert_eq!(
+            source_hash(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_update_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let outdir = dir.path();
+        for name in ["a_000.in", "b_000.in"] {
+            File::create(outdir.join(name)).unwrap();
+        }
+
+        let entry = |case: &str, seed: u32| {
+            Entry::new(
+                outdir,
+                &outdir.join(case),
+                Path::new("gen.py"),
+                "hash",
+                Some((seed, "argv".to_string())),
+                &["--n".to_string(), "10".to_string()],
+            )
+        };
+        update_manifest(outdir, vec![entry("b_000.in", 0), entry("gone_000.in", 0)]).unwrap();
+        let path =
+            update_manifest(outdir, vec![entry("a_000.in", 1), entry("b_000.in", 2)]).unwrap();
+
+        let manifest: Manifest = serde_json::from_str(&read_to_string(path).unwrap()).unwrap();
+        assert_eq!(
+            manifest.cases,
+            vec![entry("a_000.in", 1), entry("b_000.in", 2)]
+        );
+        assert_eq!(manifest.cases[0].case, "a_000.in");
+        assert_eq!(manifest.cases[0].seed, Some(1));
+        assert_eq!(manifest.cases[0].seed_via, Some("argv".to_string()));
+    }
+}
@@ -0,0 +1,289 @@
+use super::{subtask_dir, GenFileInfo};
+use anyhow::{bail, ensure, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// レシピファイル（toml）
+/// [[run]] ごとに 1 回の生成を表す
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Recipe {
+    #[serde(default)]
+    run: Vec<Run>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Run {
+    /// レシピファイルからの相対パス
+    generator: String,
+    count: Option<u32>,
+    /// seed, seed+1, ..., seed+(count-1)
+    seed: Option<u32>,
+    /// seed の後にジェネレータへ渡す引数
+    #[serde(default)]
+    args: Vec<String>,
+    /// testlib 形式の名前付き引数（-key=value として args の後に渡す）
+    #[serde(default)]
+    params: BTreeMap<String, toml::Value>,
+    /// 名前付き引数の値の一覧。直積の各組ごとに 1 回の生成とし，
+    /// 値を params と同様に渡して出力ファイル名の接頭辞に _key-value として付け加える
+    #[serde(default)]
+    matrix: BTreeMap<String, Vec<toml::Value>>,
+    /// 出力ファイル名の接頭辞（省略時はジェネレータ名）
+    name: Option<String>,
+    /// 小課題（数値 N なら outdir/subtaskN，文字列ならそのまま outdir/<subtask> に出力する）
+    subtask: Option<toml::Value>,
+    /// ジェネレータのタイムリミット（秒）。ファイル名による指定より優先する
+    timelimit: Option<f64>,
+}
+
+fn value_to_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// ファイル名に埋め込める（英数字と . + - のみからなり，".." を含まない）かどうか
+fn is_safe_name_part(s: &str) -> bool {
+    !s.is_empty()
+        && !s.contains("..")
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-'))
+}
+
+/// matrix の直積を (key, value) の組の列として返す（matrix が空なら空の組を 1 つ返す）
+fn expand_matrix(matrix: &BTreeMap<String, Vec<toml::Value>>) -> Vec<Vec<(String, String)>> {
+    let mut combinations = vec![Vec::new()];
+    for (key, values) in matrix {
+        let mut next = Vec::new();
+        for combination in &combinations {
+            for value in values {
+                let mut combination: Vec<(String, String)> = combination.clone();
+                combination.push((key.clone(), value_to_string(value)));
+                next.push(combination);
+            }
+        }
+        combinations = next;
+    }
+    combinations
+}
+
+/// レシピファイルを読み込み，生成対象の一覧を返す
+pub(super) fn load_recipe(path: &Path) -> Result<Vec<GenFileInfo>> {
+    let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    let recipe: Recipe =
+        toml::from_str(&content).with_context(|| format!("failed to parse {path:?}"))?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    let mut targets = Vec::new();
+    for run in recipe.run {
+        let generator = base.join(&run.generator);
+        ensure!(generator.exists(), "generator {generator:?} not found");
+        ensure!(
+            run.count != Some(0),
+            "count of {generator:?} must be positive"
+        );
+        ensure!(
+            run.timelimit.is_none_or(|timelimit| timelimit > 0.0),
+            "timelimit of {generator:?} must be positive"
+        );
+
+        let mut target = GenFileInfo::new(&generator)?;
+        if let Some(name) = run.name {
+            target.name = name;
+        }
+        target.count = run.count.or(target.count);
+        target.seed = run.seed;
+        target.timelimit = run.timelimit.or(target.timelimit);
+        target.subtask = match run.subtask {
+            Some(toml::Value::Integer(subtask)) => Some(subtask_dir(&subtask.to_string())?),
+            Some(toml::Value::String(subtask)) => Some(subtask_dir(&subtask)?),
+            Some(subtask) => bail!("invalid subtask {subtask} of {generator:?}"),
+            None => None,
+        };
+        target.args = run.args;
+        for (key, value) in &run.params {
+            target
+                .args
+                .push(format!("-{key}={}", value_to_string(value)));
+        }
+
+        for (key, values) in &run.matrix {
+            ensure!(
+                !values.is_empty(),
+                "matrix {key:?} of {generator:?} must not be empty"
+            );
+            // 値は出力ファイル名に埋め込むため，パスとして解釈される文字を含めない
+            for value in std::iter::once(key.clone()).chain(values.iter().map(value_to_string)) {
+                ensure!(
+                    is_safe_name_part(&value),
+                    "matrix {key:?} of {generator:?}: {value:?} may only contain letters, digits, '.', '+' and '-'"
+                );
+            }
+        }
+        for combination in expand_matrix(&run.matrix) {
+            let mut target = target.clone();
+            for (key, value) in combination {
+                target.args.push(format!("-{key}={value}"));
+                target.name += &format!("_{key}-{value}");
+            }
+            targets.push(target);
+        }
+    }
+
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{write, File};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_expand_matrix() {
+        assert_eq!(expand_matrix(&BTreeMap::new()), vec![Vec::new()]);
+
+        let matrix = BTreeMap::from([
+            (
+                "n".to_string(),
+                vec![
+                    toml::Value::Integer(10),
+                    toml::Value::String("1e5".to_string()),
+                ],
+            ),
+            (
+                "type".to_string(),
+                vec![
+                    toml::Value::String("tree".to_string()),
+                    toml::Value::String("path".to_string()),
+                ],
+            ),
+        ]);
+        let pair = |n: &str, t: &str| {
+            vec![
+                ("n".to_string(), n.to_string()),
+                ("type".to_string(), t.to_string()),
+            ]
+        };
+        assert_eq!(
+            expand_matrix(&matrix),
+            vec![
+                pair("10", "tree"),
+                pair("10", "path"),
+                pair("1e5", "tree"),
+                pair("1e5", "path"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_safe_name_part() {
+        assert!(is_safe_name_part("tree"));
+        assert!(is_safe_name_part("1e5"));
+        assert!(is_safe_name_part("-0.5"));
+        assert!(!is_safe_name_part(""));
+        assert!(!is_safe_name_part("a/b"));
+        assert!(!is_safe_name_part("two words"));
+        assert!(!is_safe_name_part(".."));
+    }
+
+    #[test]
+    fn test_load_recipe() {
+        let dir = tempfile::tempdir().unwrap();
+        File::create(dir.path().join("gen.cpp")).unwrap();
+        let recipe = dir.path().join("gen.toml");
+        write(
+            &recipe,
+            r#"
+[[run]]
+generator = "gen.cpp"
+count = 3
+seed = 100
+args = ["--n", "1000"]
+name = "large"
+params = { m = 10, type = "tree" }
+subtask = 2
+timelimit = 60.0
+
+[[run]]
+generator = "gen.cpp"
+"#,
+        )
+        .unwrap();
+
+        let targets = load_recipe(&recipe).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].path, dir.path().join("gen.cpp"));
+        assert_eq!(targets[0].name, "large");
+        assert_eq!(targets[0].count, Some(3));
+        assert_eq!(targets[0].seed, Some(100));
+        assert_eq!(
+            targets[0].args,
+            vec![
+                "--n".to_string(),
+                "1000".to_string(),
+                "-m=10".to_string(),
+                "-type=tree".to_string()
+            ]
+        );
+        assert_eq!(targets[0].subtask, Some("subtask2".to_string()));
+        assert_eq!(targets[0].timelimit, Some(60.0));
+        assert_eq!(targets[1].name, "gen");
+        assert_eq!(targets[1].subtask, None);
+        assert_eq!(targets[1].timelimit, None);
+        assert_eq!(targets[1].count, None);
+        assert_eq!(targets[1].seed, None);
+        assert!(targets[1].args.is_empty());
+
+        write(
+            &recipe,
+            "[[run]]\ngenerator = \"gen.cpp\"\nmatrix = { n = [10, 1000], type = [\"tree\"] }\n",
+        )
+        .unwrap();
+        let targets = load_recipe(&recipe).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "gen_n-10_type-tree");
+        assert_eq!(
+            targets[0].args,
+            vec!["-n=10".to_string(), "-type=tree".to_string()]
+        );
+        assert_eq!(targets[1].name, "gen_n-1000_type-tree");
+
+        write(
+            &recipe,
+            "[[run]]\ngenerator = \"gen.cpp\"\nmatrix = { n = [] }\n",
+        )
+        .unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        write(
+            &recipe,
+            "[[run]]\ngenerator = \"gen.cpp\"\nmatrix = { type = [\"../tree\"] }\n",
+        )
+        .unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        write(&recipe, "[[run]]\ngenerator = \"missing.cpp\"\n").unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        write(
+            &recipe,
+            "[[run]]\ngenerator = \"gen.cpp\"\ntimelimit = 0.0\n",
+        )
+        .unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        write(&recipe, "[[run]]\ngenerator = \"gen.cpp\"\nsubtask = 1.5\n").unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        write(&recipe, "[[run]]\ngenerator = \"gen.cpp\"\nunknown = 1\n").unwrap();
+        assert!(load_recipe(&recipe).is_err());
+
+        assert!(load_recipe(&PathBuf::from("not_found.toml")).is_err());
+    }
+}
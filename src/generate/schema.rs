@@ -0,0 +1,524 @@
+use crate::utils::SplitMix64;
+use anyhow::{bail, ensure, Context, Result};
+use clap::Args;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// スキーマファイル（.schema）から乱数でテストケースを生成する
+/// ジェネレータとして .schema を指定した場合に kuroe 自身から呼び出される
+#[derive(Debug, Args)]
+pub(crate) struct SchemaArgs {
+    /// path to the schema file
+    schema: PathBuf,
+
+    /// seed (the environment variable SEED is used if omitted)
+    seed: Option<u64>,
+
+    /// ignored
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+    rest: Vec<String>,
+}
+
+/// 整数リテラル（1e5 のような指数表記も可能）または変数
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Literal(i64),
+    Var(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// [name=]int(lo, hi)
+    Int {
+        name: Option<String>,
+        lo: Expr,
+        hi: Expr,
+    },
+    /// ints(count, lo, hi)：count 個の整数を空白区切りで並べる
+    Ints { count: Expr, lo: Expr, hi: Expr },
+    /// 値をそのまま出力する
+    Value(Expr),
+}
+
+/// スキーマの 1 行（*count で count 回繰り返す）
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    repeat: Option<Expr>,
+    tokens: Vec<Token>,
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_expr(s: &str) -> Result<Expr> {
+    let s = s.trim();
+    if is_identifier(s) {
+        return Ok(Expr::Var(s.to_string()));
+    }
+
+    let value = match s.split_once(['e', 'E']) {
+        Some((mantissa, exp)) => {
+            let mantissa = mantissa.parse::<i64>()?;
+            let exp = exp.parse::<u32>()?;
+            10i64
+                .checked_pow(exp)
+                .and_then(|base| base.checked_mul(mantissa))
+        }
+        None => s.parse::<i64>().ok(),
+    };
+    value
+        .map(Expr::Literal)
+        .with_context(|| format!("invalid value {s:?}"))
+}
+
+/// name(a, b, ...) の引数を返す
+fn parse_call<'a>(s: &'a str, name: &str, arity: usize) -> Result<Option<Vec<&'a str>>> {
+    let Some(inner) = s
+        .strip_prefix(name)
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+    else {
+        return Ok(None);
+    };
+
+    let params: Vec<&str> = inner.split(',').collect();
+    ensure!(
+        params.len() == arity,
+        "{name} takes {arity} arguments: {s:?}"
+    );
+    Ok(Some(params))
+}
+
+fn parse_token(s: &str) -> Result<Token> {
+    let (name, body) = match s.split_once('=') {
+        Some((name, body)) if !name.contains('(') => {
+            ensure!(is_identifier(name), "invalid variable name {name:?}");
+            (Some(name.to_string()), body)
+        }
+        _ => (None, s),
+    };
+
+    if let Some(params) = parse_call(body, "ints", 3)? {
+        ensure!(
+            name.is_none(),
+            "ints cannot be assigned to a variable: {s:?}"
+        );
+        return Ok(Token::Ints {
+            count: parse_expr(params[0])?,
+            lo: parse_expr(params[1])?,
+            hi: parse_expr(params[2])?,
+        });
+    }
+    if let Some(params) = parse_call(body, "int", 2)? {
+        return Ok(Token::Int {
+            name,
+            lo: parse_expr(params[0])?,
+            hi: parse_expr(params[1])?,
+        });
+    }
+    ensure!(
+        name.is_none(),
+        "only int can be assigned to a variable: {s:?}"
+    );
+    Ok(Token::Value(parse_expr(body)?))
+}
+
+/// 括弧の外側の空白で区切る
+fn split_tokens(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                ensure!(depth > 0, "unbalanced parenthesis");
+                depth -= 1;
+            }
+            _ => {}
+        }
+        if c.is_whitespace() {
+            if depth == 0 && !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(c);
+        }
+    }
+    ensure!(depth == 0, "unbalanced parenthesis");
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// スキーマを読む（# 以降はコメント，空行は無視する）
+fn parse_schema(schema: &str) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (i, line) in schema.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let mut tokens = split_tokens(line).with_context(|| format!("line {}", i + 1))?;
+        if tokens.is_empty() {
+            continue;
+        }
+
+        let repeat = match tokens[0].strip_prefix('*') {
+            Some(count) => {
+                let count = parse_expr(count).with_context(|| format!("line {}", i + 1))?;
+                tokens.remove(0);
+                Some(count)
+            }
+            None => None,
+        };
+        let tokens = tokens
+            .iter()
+            .map(|token| parse_token(token))
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("line {}", i + 1))?;
+        lines.push(Line { repeat, tokens });
+    }
+    Ok(lines)
+}
+
+fn eval(vars: &HashMap<String, i64>, expr: &Expr) -> Result<i64> {
+    match expr {
+        Expr::Literal(value) => Ok(*value),
+        Expr::Var(name) => vars
+            .get(name)
+            .copied()
+            .with_context(|| format!("undefined variable {name:?}")),
+    }
+}
+
+struct Generator {
+    rng: SplitMix64,
+    vars: HashMap<String, i64>,
+}
+
+impl Generator {
+    fn eval(&self, expr: &Expr) -> Result<i64> {
+        eval(&self.vars, expr)
+    }
+
+    /// [lo, hi] の一様乱数
+    fn random(&mut self, lo: &Expr, hi: &Expr) -> Result<i64> {
+        let (lo, hi) = (self.eval(lo)?, self.eval(hi)?);
+        ensure!(lo <= hi, "empty range [{lo}, {hi}]");
+        let width = (hi as i128 - lo as i128 + 1) as u128;
+        Ok((lo as i128 + (self.rng.next_u64() as u128 % width) as i128) as i64)
+    }
+
+    fn render_line(&mut self, line: &Line) -> Result<String> {
+        let mut values = Vec::new();
+        for token in &line.tokens {
+            match token {
+                Token::Int { name, lo, hi } => {
+                    let value = self.random(lo, hi)?;
+                    if let Some(name) = name {
+                        self.vars.insert(name.clone(), value);
+                    }
+                    values.push(value.to_string());
+                }
+                Token::Ints { count, lo, hi } => {
+                    let count = self.eval(count)?;
+                    ensure!(count >= 0, "negative count {count}");
+                    for _ in 0..count {
+                        values.push(self.random(lo, hi)?.to_string());
+                    }
+                }
+                Token::Value(expr) => values.push(self.eval(expr)?.to_string()),
+            }
+        }
+        Ok(values.join(" "))
+    }
+}
+
+/// seed からスキーマに従ったテストケースを生成する
+fn render(schema: &str, seed: u64) -> Result<String> {
+    let lines = parse_schema(schema)?;
+    let mut generator = Generator {
+        rng: SplitMix64::new(seed),
+        vars: HashMap::new(),
+    };
+
+    let mut output = String::new();
+    for line in &lines {
+        let repeat = match line.repeat {
+            Some(ref count) => generator.eval(count)?,
+            None => 1,
+        };
+        ensure!(repeat >= 0, "negative repeat count {repeat}");
+        for _ in 0..repeat {
+            output += &generator.render_line(line)?;
+            output += "\n";
+        }
+    }
+    Ok(output)
+}
+
+/// --schema の toml ファイル（.schema ファイルはそのまま読む）
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaFile {
+    /// .schema と同じ形式の入力形式
+    format: String,
+}
+
+/// スキーマを読み込む
+/// 拡張子が .toml の場合は format の値を，それ以外はファイルの内容をスキーマとする
+pub(crate) fn load_schema(path: &Path) -> Result<String> {
+    let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    let schema = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str::<SchemaFile>(&content)
+            .with_context(|| format!("failed to parse {path:?}"))?
+            .format
+    } else {
+        content
+    };
+    parse_schema(&schema).with_context(|| format!("invalid schema {path:?}"))?;
+    Ok(schema)
+}
+
+/// 整数のトークンを読む（先頭の + や余分な 0 は認めない）
+fn parse_int(token: &str) -> Result<i64> {
+    let value = token
+        .parse::<i64>()
+        .ok()
+        .filter(|value| value.to_string() == token);
+    value.with_context(|| format!("expected an integer, got {token:?}"))
+}
+
+/// 1 行分のトークンをスキーマの行と照合する
+fn check_line(
+    vars: &mut HashMap<String, i64>,
+    line: &Line,
+    tokens: &mut std::str::Split<'_, char>,
+) -> Result<()> {
+    let mut next = |expected: &str| {
+        tokens
+            .next()
+            .with_context(|| format!("expected {expected}, got end of line"))
+    };
+    let in_range = |value: i64, lo: &Expr, hi: &Expr, vars: &HashMap<String, i64>| {
+        let (lo, hi) = (eval(vars, lo)?, eval(vars, hi)?);
+        ensure!(
+            (lo..=hi).contains(&value),
+            "{value} is out of range [{lo}, {hi}]"
+        );
+        Ok(())
+    };
+
+    for token in &line.tokens {
+        match token {
+            Token::Int { name, lo, hi } => {
+                let value = parse_int(next("an integer")?)?;
+                in_range(value, lo, hi, vars)?;
+                if let Some(name) = name {
+                    vars.insert(name.clone(), value);
+                }
+            }
+            Token::Ints { count, lo, hi } => {
+                let count = eval(vars, count)?;
+                for _ in 0..count {
+                    let value = parse_int(next("an integer")?)?;
+                    in_range(value, lo, hi, vars)?;
+                }
+            }
+            Token::Value(expr) => {
+                let expected = eval(vars, expr)?;
+                let value = parse_int(next(&expected.to_string())?)?;
+                ensure!(value == expected, "expected {expected}, got {value}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// content がスキーマの形式に従っているかを検査する
+/// トークンは空白 1 つで区切られ，各行は改行で終わる必要がある
+pub(crate) fn check(schema: &str, content: &str) -> Result<()> {
+    let lines = parse_schema(schema).context("invalid schema")?;
+    let Some(content) = content.strip_suffix('\n') else {
+        bail!("the input must end with a newline");
+    };
+    let mut input = content.split('\n').enumerate();
+
+    let mut vars = HashMap::new();
+    for line in &lines {
+        let repeat = match line.repeat {
+            Some(ref count) => eval(&vars, count)?,
+            None => 1,
+        };
+        for _ in 0..repeat {
+            let Some((i, input_line)) = input.next() else {
+                bail!("expected more lines");
+            };
+            let mut tokens = input_line.split(' ');
+            check_line(&mut vars, line, &mut tokens).with_context(|| format!("line {}", i + 1))?;
+            // ints(0, ..) のみの行などトークンのない行は空行となる
+            ensure!(
+                tokens.next().is_none() || input_line.is_empty(),
+                "line {}: extra tokens or whitespace",
+                i + 1
+            );
+        }
+    }
+    if let Some((i, _)) = input.next() {
+        bail!("line {}: expected end of file", i + 1);
+    }
+    Ok(())
+}
+
+pub(crate) fn root(args: SchemaArgs) -> Result<()> {
+    let seed = match args.seed {
+        Some(seed) => seed,
+        None => match std::env::var("SEED") {
+            Ok(seed) => seed
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid SEED {seed:?}"))?,
+            Err(_) => 0,
+        },
+    };
+    let schema = read_to_string(&args.schema)
+        .with_context(|| format!("failed to read {:?}", args.schema))?;
+    match render(&schema, seed) {
+        Ok(output) => {
+            print!("{output}");
+            Ok(())
+        }
+        Err(err) => bail!("{:?}: {err:#}", args.schema),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expr() {
+        assert_eq!(parse_expr("10").unwrap(), Expr::Literal(10));
+        assert_eq!(parse_expr("-3").unwrap(), Expr::Literal(-3));
+        assert_eq!(parse_expr("1e5").unwrap(), Expr::Literal(100000));
+        assert_eq!(parse_expr("2e9").unwrap(), Expr::Literal(2000000000));
+        assert_eq!(parse_expr("n").unwrap(), Expr::Var("n".to_string()));
+        assert!(parse_expr("1e100").is_err());
+        assert!(parse_expr("1x").is_err());
+    }
+
+    #[test]
+    fn test_parse_schema() {
+        let lines = parse_schema("n=int(1, 1e5) 3 # comment\n\n*n ints(2, 1, n)\n").unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    repeat: None,
+                    tokens: vec![
+                        Token::Int {
+                            name: Some("n".to_string()),
+                            lo: Expr::Literal(1),
+                            hi: Expr::Literal(100000),
+                        },
+                        Token::Value(Expr::Literal(3)),
+                    ],
+                },
+                Line {
+                    repeat: Some(Expr::Var("n".to_string())),
+                    tokens: vec![Token::Ints {
+                        count: Expr::Literal(2),
+                        lo: Expr::Literal(1),
+                        hi: Expr::Var("n".to_string()),
+                    }],
+                },
+            ]
+        );
+
+        assert!(parse_schema("int(1, 2").is_err());
+        assert!(parse_schema("int(1)").is_err());
+        assert!(parse_schema("a=ints(1, 1, 2)").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let schema = "n=int(1, 10) m=int(0, 5)\nints(n, -1e9, 1e9)\n*m int(1, n) int(1, n)\n";
+        for seed in 0..20 {
+            let output = render(schema, seed).unwrap();
+            assert_eq!(output, render(schema, seed).unwrap());
+
+            let lines: Vec<&str> = output.lines().collect();
+            let first: Vec<i64> = lines[0].split(' ').map(|x| x.parse().unwrap()).collect();
+            let (n, m) = (first[0], first[1]);
+            assert!((1..=10).contains(&n) && (0..=5).contains(&m));
+            assert_eq!(lines.len() as i64, 2 + m);
+            assert_eq!(lines[1].split(' ').count() as i64, n);
+            for line in &lines[2..] {
+                assert!(line
+                    .split(' ')
+                    .all(|x| (1..=n).contains(&x.parse::<i64>().unwrap())));
+            }
+        }
+
+        assert!(render("int(5, 1)", 0).is_err());
+        assert!(render("int(1, n)", 0).is_err());
+    }
+
+    #[test]
+    fn test_load_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("format.toml");
+        std::fs::write(
+            &path,
+            "format = \"\"\"\nn=int(1, 10)\nints(n, 1, 10)\n\"\"\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            load_schema(&path).unwrap(),
+            "n=int(1, 10)\nints(n, 1, 10)\n"
+        );
+
+        let path = dir.path().join("format.schema");
+        std::fs::write(&path, "int(1, 10)\n").unwrap();
+        assert_eq!(load_schema(&path).unwrap(), "int(1, 10)\n");
+
+        std::fs::write(&path, "int(1, 10\n").unwrap();
+        assert!(load_schema(&path).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let schema = "n=int(1, 3) 2\nints(n, -5, 5)\n*n int(1, n)\n";
+        assert!(check(schema, "2 2\n-5 5\n1\n2\n").is_ok());
+        for seed in 0..20 {
+            assert!(check(schema, &render(schema, seed).unwrap()).is_ok());
+        }
+
+        // 範囲外
+        assert!(check(schema, "4 2\n1 1 1 1\n1\n1\n1\n1\n").is_err());
+        assert!(check(schema, "2 2\n-6 5\n1\n2\n").is_err());
+        // リテラルの不一致
+        assert!(check(schema, "2 3\n-5 5\n1\n2\n").is_err());
+        // 空白や改行の誤り
+        assert!(check(schema, "2  2\n-5 5\n1\n2\n").is_err());
+        assert!(check(schema, "2 2 \n-5 5\n1\n2\n").is_err());
+        assert!(check(schema, "2 2\n-5 5\n1\n2").is_err());
+        assert!(check(schema, "2 2\n-5 5\n1\n2\n\n").is_err());
+        // 不正な整数
+        assert!(check(schema, "02 2\n-5 5\n1\n2\n").is_err());
+        assert!(check(schema, "+2 2\n-5 5\n1\n2\n").is_err());
+        // 行数の過不足
+        assert!(check(schema, "2 2\n-5 5\n1\n").is_err());
+        assert!(check(schema, "2 2\n-5 5\n1\n2\n3\n").is_err());
+
+        // トークンのない行
+        assert!(check("n=int(0, 1)\nints(n, 1, 1)\n", "0\n\n").is_ok());
+        assert!(check("n=int(0, 1)\nints(n, 1, 1)\n", "0\n \n").is_err());
+    }
+}
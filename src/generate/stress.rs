@@ -0,0 +1,172 @@
+use super::{pass_seed, with_envs, GenFileInfo, GenerateArgs};
+use crate::language::{compile_and_get_runstep, CommandStep, ExecuteStatus, Language};
+use crate::utils::SplitMix64;
+use anyhow::{bail, ensure, Context, Result};
+use log::info;
+use std::fs::{copy, create_dir_all, read_to_string, File};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// 空白区切りのトークン列として比較する
+fn same_tokens(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/// 食い違いの表示に用いる出力の最大行数
+const OUTPUT_LIMIT: usize = 10;
+
+/// ジェネレータが連続してこの回数だけ失敗した場合は打ち切る
+const MAX_GENERATOR_FAILURES: u32 = 10;
+
+/// path の先頭 OUTPUT_LIMIT 行を返す
+fn head(path: &Path) -> String {
+    let content = read_to_string(path).unwrap_or_default();
+    let lines: Vec<_> = content.lines().take(OUTPUT_LIMIT).collect();
+    lines.join("\n")
+}
+
+/// input を解いた結果を output に書き込む
+fn run(
+    dir: &Path,
+    runstep: &CommandStep,
+    input: &Path,
+    output: &Path,
+    timelimit: f64,
+) -> Result<ExecuteStatus> {
+    runstep.execute(
+        dir,
+        Vec::new(),
+        File::open(input)?,
+        File::create(output)?,
+        Stdio::null(),
+        Duration::from_secs_f64(timelimit),
+    )
+}
+
+/// ランダムな seed で生成と solver, brute の実行を繰り返し，結果が食い違った時点で停止する
+/// 食い違ったケースは outdir/{name}_stress_{seed}.in に保存し，エラーを返す
+pub(super) fn stress(
+    generators: &[GenFileInfo],
+    langs: &Vec<Box<dyn Language>>,
+    args: &GenerateArgs,
+) -> Result<()> {
+    ensure!(!generators.is_empty(), "--stress requires a generator");
+
+    let solver = args.solver.as_ref().unwrap();
+    let brute = args.brute.as_ref().unwrap();
+
+    let solver_dir = TempDir::new()?;
+    let solver_step = compile_and_get_runstep(&solver_dir, solver, langs)
+        .with_context(|| format!("failed to compile {solver:?}"))?;
+    let brute_dir = TempDir::new()?;
+    let brute_step = compile_and_get_runstep(&brute_dir, brute, langs)
+        .with_context(|| format!("failed to compile {brute:?}"))?;
+
+    let mut compiled = Vec::new();
+    for target in generators {
+        let dir = TempDir::new()?;
+        let runstep = compile_and_get_runstep(&dir, &target.path, langs)
+            .with_context(|| format!("failed to compile {:?}", target.path))?;
+        let runstep = with_envs(runstep, &args.env);
+        compiled.push((target, dir, runstep));
+    }
+
+    let work = TempDir::new()?;
+    let input = work.path().join("stress.in");
+    let solver_output = work.path().join("solver.out");
+    let brute_output = work.path().join("brute.out");
+
+    let mut rng = SplitMix64::from_time();
+    let mut iteration: u64 = 0;
+    let mut failures: u32 = 0;
+    loop {
+        if args.iterations.is_some_and(|max| iteration >= max) {
+            println!("no counterexample found in {iteration} iterations");
+            return Ok(());
+        }
+
+        let (target, dir, runstep) = &compiled[iteration as usize % compiled.len()];
+        iteration += 1;
+        let seed = rng.next_u64() as u32;
+
+        let (runstep, seed_args, stdin) =
+            pass_seed(runstep, &args.seed_via, seed, &target.args, work.path())?;
+        let status = runstep.execute(
+            dir,
+            seed_args,
+            stdin,
+            File::create(&input)?,
+            Stdio::null(),
+            Duration::from_secs_f64(target.timelimit(args)),
+        )?;
+        if !status.success() {
+            println!(
+                "[STRESS] {:?} failed at seed = {seed} ({status})",
+                target.path
+            );
+            failures += 1;
+            if failures >= MAX_GENERATOR_FAILURES {
+                bail!("generators failed {failures} times in a row");
+            }
+            continue;
+        }
+        failures = 0;
+
+        let solver_status = run(
+            solver_dir.path(),
+            &solver_step,
+            &input,
+            &solver_output,
+            args.timelimit,
+        )?;
+        let brute_status = run(
+            brute_dir.path(),
+            &brute_step,
+            &input,
+            &brute_output,
+            args.timelimit,
+        )?;
+        info!(
+            "[STRESS] #{iteration} seed = {seed}, solver = {solver_status}, brute = {brute_status}"
+        );
+
+        let agreed = solver_status.success()
+            && brute_status.success()
+            && same_tokens(
+                &read_to_string(&solver_output)?,
+                &read_to_string(&brute_output)?,
+            );
+        if agreed {
+            continue;
+        }
+
+        if !args.outdir.exists() {
+            create_dir_all(&args.outdir)?;
+        }
+        let saved = args
+            .outdir
+            .join(format!("{}_stress_{seed}.in", target.name));
+        copy(&input, &saved)?;
+
+        println!("counterexample found at iteration {iteration}");
+        println!("generator = {:?}, seed = {seed}", target.path);
+        println!("input = {saved:?}");
+        println!("solver ({solver_status}):\n{}", head(&solver_output));
+        println!("brute ({brute_status}):\n{}", head(&brute_output));
+        bail!("solver and brute disagree at seed = {seed}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_tokens() {
+        assert!(same_tokens("1 2\n3\n", "1  2 3"));
+        assert!(!same_tokens("1 2", "1 2 3"));
+        assert!(!same_tokens("12", "1 2"));
+    }
+}
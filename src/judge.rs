@@ -1,15 +1,27 @@
-use crate::language::{compile_and_get_runstep, CommandStep, ExecuteStatus, Language};
-use crate::utils::{find_files, make_languages};
-use anyhow::{bail, ensure, Result};
+mod report;
+
+use crate::language::{
+    compile_and_get_steps, compile_and_get_steps_with_log, CommandStep, ExecuteStatus, Language,
+};
+use crate::utils::{
+    find_files, glob_to_regex, inflate_input, input_name, input_with_extension, is_gzip, is_input,
+    is_selected, make_languages, shuffle, SplitMix64,
+};
+use anyhow::{bail, ensure, Context, Result};
 use clap::{Args, ValueEnum};
-use indicatif::{ProgressBar, ProgressStyle};
+use console::{style, truncate_str};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use log::{info, warn};
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
+use std::env::current_dir;
+use std::ffi::{OsStr, OsString};
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write, File};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::{Duration, Instant};
-use tabled::{Table, Tabled};
+use std::thread;
+use std::time::Duration;
+use tabled::settings::{object::Segment, Format, Style};
+use tabled::{builder::Builder, Table, Tabled};
 use tempfile::TempDir;
 
 #[derive(Debug, Args)]
@@ -22,26 +34,119 @@ pub(super) struct JudgeArgs {
     #[arg(short, long, default_value_t = false)]
     recursive: bool,
 
+    /// calling convention of the checker
+    #[arg(long, value_enum, default_value_t = CheckerProtocol::Testlib)]
+    checker_protocol: CheckerProtocol,
+
+    /// grader sources compiled together with each solver (headers are added to the include path)
+    #[arg(long, value_name = "FILE", num_args = 1..)]
+    grader: Vec<PathBuf>,
+
     /// path to the checker
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "compare_cmd")]
     checker: Option<PathBuf>,
 
+    /// shell command used as the comparator instead of a compiled checker (called with <input> <output> <answer>)
+    #[arg(long, value_name = "CMD")]
+    compare_cmd: Option<String>,
+
     /// directory containing the testcases(*.in and *.ans)
     #[arg(short, long, default_value = "./testcases")]
     testcases: Vec<PathBuf>,
 
-    ///
+    /// directory containing the answers(*.ans) laid out in parallel with the testcases
+    #[arg(long, value_name = "DIR")]
+    answer_dir: Option<PathBuf>,
+
+    /// output directory
     #[arg(short, long, default_value = "./testcases/output")]
     outdir: PathBuf,
 
+    /// judge only testcases whose name matches the glob pattern (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    filter: Vec<String>,
+
+    /// skip testcases whose name matches the glob pattern (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// judge the testcases in a random order determined by SEED (chosen randomly if omitted)
+    #[arg(long, value_name = "SEED")]
+    shuffle: Option<Option<u64>>,
+
+    /// judge only testcases having any of the tags listed in <CASE>.tags (repeatable)
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+
     /// timelimit for solver
     #[arg(visible_alias = "tl", long, default_value_t = 2.0)]
     timelimit: f64,
 
+    /// maximum size of the solver output in MiB
+    #[arg(long, value_name = "MiB", default_value_t = 256)]
+    output_limit: u64,
+
+    /// run each testcase N times and report min/avg/max time
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+
+    /// flag AC cases whose time is within MARGIN percent of the timelimit
+    #[arg(
+        long,
+        value_name = "MARGIN",
+        num_args = 0..=1,
+        default_missing_value = "20"
+    )]
+    near_timelimit: Option<f64>,
+
+    /// map a checker exit code or output keyword to a verdict: <KEY>=<VERDICT>[:<SCORE>] (repeatable)
+    #[arg(long, value_name = "<KEY>=<VERDICT>[:<SCORE>]", value_parser = parse_verdict_rule)]
+    verdict_map: Vec<VerdictRule>,
+
     /// judge policy
     #[arg(short, long, value_enum, default_value_t = JudgePolicy::All)]
     policy: JudgePolicy,
 
+    /// save the solver's stderr to <OUTDIR>/<SOLVER>/<CASE>.err
+    #[arg(long, default_value_t = false)]
+    save_stderr: bool,
+
+    /// show the first differing lines between the output and the answer for WA cases
+    #[arg(long, default_value_t = false)]
+    show_diff: bool,
+
+    /// write the results as JUnit XML
+    #[arg(long, value_name = "PATH")]
+    junit: Option<PathBuf>,
+
+    /// write a standalone HTML report
+    #[arg(long, value_name = "PATH")]
+    html: Option<PathBuf>,
+
+    /// show testcase paths relative to the testcase directory in the tables
+    #[arg(long, default_value_t = false)]
+    relative_paths: bool,
+
+    /// truncate table cells longer than N characters
+    #[arg(long, value_name = "N")]
+    max_width: Option<usize>,
+
+    /// style of the result tables
+    #[arg(long, value_enum, default_value_t = TableStyle::Ascii)]
+    table_style: TableStyle,
+
+    /// judge all solvers concurrently. Timings may become less stable
+    #[arg(long, default_value_t = false)]
+    parallel: bool,
+
+    /// print every verdict as soon as it is decided. Failures are always printed
+    #[arg(long, default_value_t = false)]
+    live: bool,
+
+    /// stop judging a solver once this many cases have failed
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_failures: Option<u32>,
+
     /// COMMAND[0:-1] are the compile commands. COMMAND[-1] is execute command
     #[arg(
         short,
@@ -53,6 +158,31 @@ pub(super) struct JudgeArgs {
     language: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum CheckerProtocol {
+    /// checker <input> <output> <answer>, accepted iff exit code is 0
+    Testlib,
+
+    /// checker <input> <answer> <feedback_dir> < output, exit code 42 (AC) or 43 (WA)
+    Icpc,
+}
+
+/// ICPC output validator の終了コード
+const ICPC_ACCEPTED: i32 = 42;
+const ICPC_WRONG_ANSWER: i32 = 43;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum TableStyle {
+    /// Bordered table
+    Ascii,
+
+    /// Markdown table. Line breaks in cells become <br>
+    Markdown,
+
+    /// Table without outer borders and row separators
+    Compact,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
 enum JudgePolicy {
     /// Run all cases anyway
@@ -60,6 +190,20 @@ enum JudgePolicy {
 
     /// TLE ends the judge
     TLEBreak,
+
+    /// Any verdict other than AC ends the judge
+    WABreak,
+}
+
+impl JudgePolicy {
+    /// verdict が出た後にジャッジを打ち切るかどうか
+    fn should_break(&self, verdict: &Verdict) -> bool {
+        match self {
+            JudgePolicy::All => false,
+            JudgePolicy::TLEBreak => *verdict == Verdict::TimeLimitExceed,
+            JudgePolicy::WABreak => *verdict != Verdict::Accepted,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -68,6 +212,16 @@ struct JudgeInfo {
     answer_path: Option<PathBuf>,
     output_path: Option<PathBuf>,
     status: Option<ExecuteStatus>,
+    timelimit: Option<f64>,
+    elapsed: Option<Duration>,
+    cpu_time: Option<Duration>,
+    memory: Option<u64>,
+    stderr_path: Option<PathBuf>,
+    runs: Vec<Duration>,
+    /// outdir/<solver>/ 以下の出力先サブディレクトリ
+    subdir: PathBuf,
+    /// 別解（{name}.ans.2, {name}.ans.3, ...）
+    alt_answers: Vec<PathBuf>,
 }
 
 impl JudgeInfo {
@@ -77,6 +231,14 @@ impl JudgeInfo {
             answer_path: None,
             output_path: None,
             status: None,
+            timelimit: None,
+            elapsed: None,
+            cpu_time: None,
+            memory: None,
+            stderr_path: None,
+            runs: Vec::new(),
+            subdir: PathBuf::new(),
+            alt_answers: Vec::new(),
         }
     }
 
@@ -96,6 +258,41 @@ impl JudgeInfo {
         self.status = Some(status);
         self
     }
+    fn timelimit(mut self, timelimit: f64) -> Self {
+        self.timelimit = Some(timelimit);
+        self
+    }
+    fn elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+    fn cpu_time(mut self, cpu_time: Duration) -> Self {
+        self.cpu_time = Some(cpu_time);
+        self
+    }
+    /// KiB
+    fn memory(mut self, memory: u64) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// 各実行の実行時間
+    fn runs(mut self, runs: Vec<Duration>) -> Self {
+        self.runs = runs;
+        self
+    }
+    fn stderr(mut self, path: &Path) -> Self {
+        self.stderr_path = Some(path.to_path_buf());
+        self
+    }
+    fn subdir(mut self, path: &Path) -> Self {
+        self.subdir = path.to_path_buf();
+        self
+    }
+    fn alt_answers(mut self, paths: Vec<PathBuf>) -> Self {
+        self.alt_answers = paths;
+        self
+    }
 
     fn get_input_path(&self) -> Option<&PathBuf> {
         self.input_path.as_ref()
@@ -106,56 +303,534 @@ impl JudgeInfo {
     fn get_output_path(&self) -> Option<&PathBuf> {
         self.output_path.as_ref()
     }
+    fn get_stderr_path(&self) -> Option<&PathBuf> {
+        self.stderr_path.as_ref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceed,
+    RuntimeError,
+    OutputLimitExceed,
+    /// checker 自体が異常終了した
+    JudgeError,
+    CompileError,
+    Skipped,
+    /// --verdict-map で定義された判定
+    Custom(String),
+}
+
+impl From<&str> for Verdict {
+    fn from(name: &str) -> Self {
+        match name {
+            "AC" => Verdict::Accepted,
+            "WA" => Verdict::WrongAnswer,
+            "TLE" => Verdict::TimeLimitExceed,
+            "RE" => Verdict::RuntimeError,
+            "OLE" => Verdict::OutputLimitExceed,
+            "JE" => Verdict::JudgeError,
+            "CE" => Verdict::CompileError,
+            _ => Verdict::Custom(name.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Verdict::Accepted => write!(f, "AC")?,
+            Verdict::WrongAnswer => write!(f, "WA")?,
+            Verdict::TimeLimitExceed => write!(f, "TLE")?,
+            Verdict::RuntimeError => write!(f, "RE")?,
+            Verdict::OutputLimitExceed => write!(f, "OLE")?,
+            Verdict::JudgeError => write!(f, "JE")?,
+            Verdict::CompileError => write!(f, "CE")?,
+            Verdict::Skipped => write!(f, "SKIP")?,
+            Verdict::Custom(name) => write!(f, "{name}")?,
+        };
+
+        Ok(())
+    }
+}
+
+impl Verdict {
+    /// 端末出力用に色付けした文字列を返す
+    /// NO_COLOR や非 TTY の場合は色付けされない
+    fn styled(&self) -> String {
+        let text = style(self.to_string());
+        match self {
+            Verdict::Accepted => text.green(),
+            Verdict::WrongAnswer => text.red(),
+            Verdict::TimeLimitExceed => text.yellow(),
+            Verdict::RuntimeError => text.magenta(),
+            Verdict::OutputLimitExceed => text.magenta(),
+            Verdict::JudgeError => text.red().bold(),
+            Verdict::CompileError => text.red().bold(),
+            Verdict::Skipped => text.dim(),
+            Verdict::Custom(_) => text.cyan(),
+        }
+        .to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum VerdictKey {
+    ExitCode(i32),
+    Keyword(String),
+}
+
+/// checker の終了コードまたは出力中のキーワードから判定への対応
+/// `<KEY>=<VERDICT>[:<SCORE>]` の形式で指定する
+#[derive(Debug, Clone, PartialEq)]
+struct VerdictRule {
+    key: VerdictKey,
+    verdict: Verdict,
+    score: Option<f64>,
+}
+
+impl VerdictRule {
+    fn matches(&self, exit_code: Option<i32>, message: &str) -> bool {
+        match self.key {
+            VerdictKey::ExitCode(code) => exit_code == Some(code),
+            VerdictKey::Keyword(ref keyword) => message.contains(keyword.as_str()),
+        }
+    }
+}
+
+fn parse_verdict_rule(s: &str) -> Result<VerdictRule, String> {
+    let (key, value) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("invalid verdict mapping {s:?}: expected <KEY>=<VERDICT>"))?;
+    if key.is_empty() {
+        return Err(format!("invalid verdict mapping {s:?}: empty key"));
+    }
+    let key = match key.parse::<i32>() {
+        Ok(code) => VerdictKey::ExitCode(code),
+        Err(_) => VerdictKey::Keyword(key.to_string()),
+    };
+
+    let (verdict, score) = match value.split_once(':') {
+        Some((verdict, score)) => {
+            let score = score
+                .parse::<f64>()
+                .map_err(|_| format!("invalid score in verdict mapping {s:?}"))?;
+            (verdict, Some(score))
+        }
+        None => (value, None),
+    };
+    if verdict.is_empty() {
+        return Err(format!("invalid verdict mapping {s:?}: empty verdict"));
+    }
+
+    Ok(VerdictRule {
+        key,
+        verdict: Verdict::from(verdict),
+        score,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct CaseResult {
+    verdict: Verdict,
+    info: JudgeInfo,
+    message: Option<String>,
+    diff: Vec<String>,
+    near_timelimit: bool,
+    score: Option<f64>,
+}
+
+impl CaseResult {
+    fn new(verdict: Verdict, info: JudgeInfo) -> Self {
+        Self {
+            verdict,
+            info,
+            message: None,
+            diff: Vec::new(),
+            near_timelimit: false,
+            score: None,
+        }
+    }
+
+    fn message(mut self, message: Option<String>) -> Self {
+        self.message = message;
+        self
+    }
+
+    fn diff(mut self, diff: Vec<String>) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    fn near_timelimit(mut self, near_timelimit: bool) -> Self {
+        self.near_timelimit = near_timelimit;
+        self
+    }
+
+    fn score(mut self, score: Option<f64>) -> Self {
+        self.score = score;
+        self
+    }
+
+    /// 結果表の info 欄
+    /// 失敗したケースで標準エラー出力を保存している場合はそのパスも含める
+    fn describe(&self) -> String {
+        let description = self.describe_verdict();
+        match (&self.verdict, self.info.get_stderr_path()) {
+            (Verdict::Accepted | Verdict::Skipped, _) | (_, None) => description,
+            (_, Some(path)) if description.is_empty() => format!("{path:?}"),
+            (_, Some(path)) => format!("{description}\n{path:?}"),
+        }
+    }
+
+    fn describe_verdict(&self) -> String {
+        match self.verdict {
+            Verdict::JudgeError => self.message.clone().unwrap_or_default(),
+            Verdict::Custom(_) => {
+                let mut lines = Vec::new();
+                lines.extend(self.score.map(|score| format!("score = {score}")));
+                lines.extend(self.message.clone());
+                lines.join("\n")
+            }
+            Verdict::Accepted => {
+                let time = match timing_stats(&self.info.runs) {
+                    Some((min, avg, max)) if self.info.runs.len() > 1 => {
+                        format!("time = {min:?} / {avg:?} / {max:?} (min/avg/max)")
+                    }
+                    _ => format!("time = {:?}", self.info.elapsed.unwrap()),
+                };
+                let time = if self.near_timelimit {
+                    style(format!("{time} (near timelimit)"))
+                        .yellow()
+                        .to_string()
+                } else {
+                    time
+                };
+                format!(
+                    "{time}\ncpu = {:?}\nmemory = {}",
+                    self.info.cpu_time.unwrap(),
+                    format_memory(self.info.memory.unwrap())
+                )
+            }
+            Verdict::WrongAnswer => {
+                let mut lines = vec![format!("{:?}", self.info.get_output_path().unwrap())];
+                lines.extend(self.message.clone());
+                lines.extend(self.diff.iter().cloned());
+                lines.join("\n")
+            }
+            _ => "".to_string(),
+        }
+    }
+}
+
+/// 実行時間の (最小, 平均, 最大) を返す
+fn timing_stats(runs: &[Duration]) -> Option<(Duration, Duration, Duration)> {
+    let min = *runs.iter().min()?;
+    let max = *runs.iter().max()?;
+    let avg = runs.iter().sum::<Duration>() / runs.len() as u32;
+    Some((min, avg, max))
+}
+
+/// 実行時間がタイムリミットの margin % 以内に迫っているかどうか
+fn is_near_timelimit(elapsed: Duration, timelimit: f64, margin: f64) -> bool {
+    elapsed.as_secs_f64() >= timelimit * (1.0 - margin / 100.0)
+}
+
+/// KiB 単位のメモリ量を表示用に整形する
+fn format_memory(kib: u64) -> String {
+    if kib < 1024 {
+        format!("{kib} KiB")
+    } else {
+        format!("{:.1} MiB", kib as f64 / 1024.0)
+    }
+}
+
+/// solver ごとの集計
+#[derive(Debug)]
+struct JudgeSummary {
+    verdicts: Vec<Verdict>,
+    slowest: Option<(PathBuf, Duration)>,
+}
+
+impl JudgeSummary {
+    fn new(results: &[CaseResult]) -> Self {
+        let verdicts = results
+            .iter()
+            .map(|result| result.verdict.clone())
+            .collect();
+        let slowest = results
+            .iter()
+            .filter_map(|result| {
+                let elapsed = result.info.elapsed?;
+                let input = result.info.get_input_path()?;
+                Some((input.clone(), elapsed))
+            })
+            .max_by_key(|(_, elapsed)| *elapsed);
+
+        Self { verdicts, slowest }
+    }
+
+    fn count(&self, verdict: Verdict) -> usize {
+        self.verdicts.iter().filter(|v| **v == verdict).count()
+    }
+
+    /// AC/WA/TLE/RE/SKIP 以外の件数
+    fn count_other(&self) -> usize {
+        self.verdicts
+            .iter()
+            .filter(|v| {
+                matches!(
+                    v,
+                    Verdict::OutputLimitExceed
+                        | Verdict::JudgeError
+                        | Verdict::CompileError
+                        | Verdict::Custom(_)
+                )
+            })
+            .count()
+    }
+
+    /// 全ケースが AC の場合のみ pass
+    fn passed(&self) -> bool {
+        !self.verdicts.is_empty() && self.count(Verdict::Accepted) == self.verdicts.len()
+    }
 }
 
 /// .in と .ans が揃っているケースを列挙
-/// ファイル名の重複は未定義動作
-fn enumerate_valid_testcases(all_cases: &Vec<PathBuf>) -> Vec<JudgeInfo> {
+/// .in の共通祖先からの相対パスと .ans の共通祖先からの相対パスが一致するものを組にする
+/// （input/subtask1/a.in は answer/subtask1/a.ans と組になる）
+/// 一致するものがなければファイル名が一致する .ans を使う（同名の .ans が複数あれば組にしない）
+fn enumerate_valid_testcases(all_cases: &[PathBuf]) -> Vec<JudgeInfo> {
+    let parent = |case: &PathBuf| case.parent().unwrap_or(Path::new("")).to_path_buf();
+    let inputs: Vec<&PathBuf> = all_cases.iter().filter(|case| is_input(case)).collect();
+    let answers: Vec<&PathBuf> = all_cases
+        .iter()
+        .filter(|case| case.extension().is_some_and(|ext| ext == "ans"))
+        .collect();
+    let input_root = common_dir(&inputs.iter().map(|case| parent(case)).collect::<Vec<_>>());
+    let answer_root = common_dir(&answers.iter().map(|case| parent(case)).collect::<Vec<_>>());
+
+    // (共通祖先からの相対ディレクトリ, ファイル名) -> .ans
     let mut ans_cases = HashMap::new();
-    for case in all_cases.iter() {
-        if case.extension().map_or(false, |ext| ext == "ans") {
-            ans_cases.insert(case.file_stem().unwrap(), case);
+    let mut ans_names: HashMap<String, Vec<&PathBuf>> = HashMap::new();
+    for case in answers {
+        let name = case.file_stem().unwrap().to_string_lossy().to_string();
+        let relative = parent(case)
+            .strip_prefix(&answer_root)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        ans_cases.insert((relative, name.clone()), case);
+        ans_names.entry(name).or_default().push(case);
+    }
+
+    let mut valid_cases = Vec::new();
+    for case in inputs {
+        let base_name = input_name(case);
+        let relative = parent(case)
+            .strip_prefix(&input_root)
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+
+        let ans_path = ans_cases
+            .get(&(relative, base_name.clone()))
+            .copied()
+            .or_else(|| match ans_names.get(&base_name).map(Vec::as_slice) {
+                Some([ans_path]) => Some(*ans_path),
+                _ => None,
+            });
+        if let Some(ans_path) = ans_path {
+            valid_cases.push(JudgeInfo::new().input(case).answer(ans_path));
+        }
+    }
+
+    valid_cases.sort_by(|x, y| x.get_input_path().cmp(&y.get_input_path()));
+    valid_cases
+}
+
+/// testcases 以下の .in に対応する .ans を answer_dir から探して列挙
+/// base からの相対パスが一致する .ans を優先し，なければ answer_dir 以下でファイル名が一致する .ans を使う
+/// （同名の .ans が複数あれば組にしない）
+fn enumerate_testcases_with_answer_dir(
+    bases: &[PathBuf],
+    answer_dir: &Path,
+) -> Result<Vec<JudgeInfo>> {
+    let mut ans_names: HashMap<OsString, Vec<PathBuf>> = HashMap::new();
+    for case in find_files(answer_dir, true)? {
+        if case.extension().is_some_and(|ext| ext == "ans") {
+            ans_names
+                .entry(case.file_stem().unwrap().to_os_string())
+                .or_default()
+                .push(case);
         }
     }
 
     let mut valid_cases = Vec::new();
-    for case in all_cases {
-        if case.extension().map_or(false, |ext| ext == "in") {
-            let base_name = case.file_stem().unwrap();
+    for base in bases {
+        for case in find_files(base, true)? {
+            if !is_input(&case) {
+                continue;
+            }
 
-            if let Some(ans_path) = ans_cases.get(base_name) {
+            let relative = match case.strip_prefix(base) {
+                Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+                _ => PathBuf::from(case.file_name().unwrap()),
+            };
+            let ans_path = input_with_extension(&answer_dir.join(relative), "ans");
+            if ans_path.is_file() {
                 valid_cases.push(JudgeInfo::new().input(&case).answer(&ans_path));
+            } else if let Some([ans_path]) = ans_names
+                .get(OsStr::new(&input_name(&case)))
+                .map(Vec::as_slice)
+            {
+                valid_cases.push(JudgeInfo::new().input(&case).answer(ans_path));
             }
         }
     }
 
     valid_cases.sort_by(|x, y| x.get_input_path().cmp(&y.get_input_path()));
-    valid_cases
+    Ok(valid_cases)
+}
+
+/// dirs の共通祖先（dirs が空なら空のパス）
+fn common_dir(dirs: &[PathBuf]) -> PathBuf {
+    let Some(first) = dirs.first() else {
+        return PathBuf::new();
+    };
+
+    let mut common = first.clone();
+    for dir in &dirs[1..] {
+        while !dir.starts_with(&common) {
+            if !common.pop() {
+                break;
+            }
+        }
+    }
+    common
 }
 
-/// output 出力先を返す
+/// 全ケースの input のディレクトリの共通祖先を基準に，出力先サブディレクトリを設定する
+/// testcases/input/subtask1/a.in は outdir/<solver>/subtask1/a.out に出力される
+fn assign_subdirs(cases: Vec<JudgeInfo>) -> Vec<JudgeInfo> {
+    let dirs: Vec<_> = cases
+        .iter()
+        .map(|case| {
+            case.get_input_path()
+                .unwrap()
+                .parent()
+                .unwrap_or(Path::new(""))
+                .to_path_buf()
+        })
+        .collect();
+    if dirs.is_empty() {
+        return cases;
+    }
+    let common = common_dir(&dirs);
+
+    cases
+        .into_iter()
+        .zip(&dirs)
+        .map(|(case, dir)| {
+            let subdir = dir.strip_prefix(&common).unwrap_or(Path::new(""));
+            case.subdir(subdir)
+        })
+        .collect()
+}
+
+/// input と同じディレクトリの {name}.tl から個別のタイムリミット（秒）を読み込む
+/// ファイルが存在しない場合は None
+fn read_timelimit(input: &Path) -> Result<Option<f64>> {
+    let path = input_with_extension(input, "tl");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = read_to_string(&path)?;
+    let timelimit = content
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("invalid timelimit in {path:?}"))?;
+    ensure!(timelimit > 0.0, "timelimit in {path:?} must be positive");
+
+    Ok(Some(timelimit))
+}
+
+/// answer の別解 {answer}.2, {answer}.3, ... を番号順に列挙する
+/// 番号が途切れた時点で終了する
+fn find_alt_answers(answer: &Path) -> Vec<PathBuf> {
+    let mut answers = Vec::new();
+    for i in 2.. {
+        let mut path = answer.as_os_str().to_os_string();
+        path.push(format!(".{i}"));
+        let path = PathBuf::from(path);
+        if !path.exists() {
+            break;
+        }
+        answers.push(path);
+    }
+    answers
+}
+
+/// input と同じディレクトリの {name}.tags からタグ（空白またはカンマ区切り）を読み込む
+/// ファイルが存在しない場合は空
+pub(crate) fn read_tags(input: &Path) -> Result<Vec<String>> {
+    let path = input_with_extension(input, "tags");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = read_to_string(&path)?;
+    Ok(content
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// target を実行し，実行結果，output 出力先，資源使用量を記録して返す
+/// save_stderr の場合は標準エラー出力を {name}.err に保存する
 fn solve<P: AsRef<Path>>(
     current_dir: P,
-    target: &Path,
+    target: JudgeInfo,
     outdir: &Path,
     run: &CommandStep,
     timelimit: f64,
-) -> Result<(ExecuteStatus, PathBuf)> {
-    let input = File::open(&target)?;
+    save_stderr: bool,
+) -> Result<JudgeInfo> {
+    let input_path = target.get_input_path().unwrap();
+    let input = File::open(input_path)?;
 
-    let name = target.file_stem().unwrap().to_string_lossy().to_string();
+    let name = input_name(input_path);
     let output_path = outdir.join(format!("{name}.out"));
     let output = File::create(&output_path)?;
 
-    if let Ok(status) = run.execute(
+    let stderr_path = outdir.join(format!("{name}.err"));
+    let stderr = if save_stderr {
+        Stdio::from(File::create(&stderr_path)?)
+    } else {
+        Stdio::null()
+    };
+
+    if let Ok((status, detail)) = run.execute_with_detail(
         current_dir,
         Vec::new(),
         input,
         output,
-        Stdio::null(),
+        stderr,
         Duration::from_secs_f64(timelimit),
     ) {
-        Ok((status, output_path))
+        let target = target
+            .output(&output_path)
+            .status(status)
+            .elapsed(detail.elapsed)
+            .cpu_time(detail.cpu_time)
+            .memory(detail.max_rss);
+        if save_stderr {
+            Ok(target.stderr(&stderr_path))
+        } else {
+            Ok(target)
+        }
     } else {
         bail!("failed to run")
     }
@@ -163,12 +838,6 @@ fn solve<P: AsRef<Path>>(
 
 /// 完全一致ジャッジ
 fn judge_by_diff<P: AsRef<Path>>(current_dir: P, info: &JudgeInfo) -> Result<bool> {
-    let answer = info
-        .get_answer_path()
-        .unwrap()
-        .canonicalize()?
-        .to_string_lossy()
-        .to_string();
     let output = info
         .get_output_path()
         .unwrap()
@@ -176,20 +845,48 @@ fn judge_by_diff<P: AsRef<Path>>(current_dir: P, info: &JudgeInfo) -> Result<boo
         .to_string_lossy()
         .to_string();
 
-    Ok(CommandStep::new(format!("diff"), Vec::new())
-        .execute(
-            current_dir,
-            vec![answer, output],
+    // いずれかの解答と一致すれば AC
+    let answers = std::iter::once(info.get_answer_path().unwrap()).chain(&info.alt_answers);
+    for answer in answers {
+        let answer = answer.canonicalize()?.to_string_lossy().to_string();
+        let status = CommandStep::new("diff".to_string(), Vec::new()).execute(
+            &current_dir,
+            vec![answer, output.clone()],
             Stdio::null(),
             Stdio::null(),
             Stdio::null(),
             Duration::from_secs(10),
-        )?
-        .success())
+        )?;
+        if status.success() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
-// checker によるジャッジ
-fn judge<P: AsRef<Path>>(current_dir: P, info: &JudgeInfo, run: &CommandStep) -> Result<bool> {
+/// checker による判定結果
+#[derive(Debug, Clone, PartialEq)]
+enum CheckerStatus {
+    Accepted,
+    Rejected,
+    /// checker 自体の異常終了（理由）
+    Failed(String),
+}
+
+/// testlib の checker が内部エラー時に返す終了コード
+const TESTLIB_FAIL: i32 = 3;
+
+/// checker によるジャッジ
+/// checker の標準出力と標準エラー出力は message_path に保存される
+/// 判定結果と checker の終了コードを返す
+/// checker の異常終了（シグナル，タイムアウト，testlib の FAIL）は CheckerStatus::Failed となる
+fn judge<P: AsRef<Path>>(
+    current_dir: P,
+    info: &JudgeInfo,
+    run: &CommandStep,
+    message_path: &Path,
+    protocol: CheckerProtocol,
+) -> Result<(CheckerStatus, Option<i32>)> {
     let input = info
         .get_input_path()
         .unwrap()
@@ -208,160 +905,606 @@ fn judge<P: AsRef<Path>>(current_dir: P, info: &JudgeInfo, run: &CommandStep) ->
         .canonicalize()?
         .to_string_lossy()
         .to_string();
+    let message = File::create(message_path)?;
 
-    if let Ok(status) = run.execute(
-        current_dir,
-        vec![input, output, answer],
-        Stdio::null(),
-        Stdio::null(),
-        Stdio::null(),
-        Duration::from_secs(10),
-    ) {
-        Ok(status.success())
+    match protocol {
+        CheckerProtocol::Testlib => {
+            if let Ok((status, detail)) = run.execute_with_detail(
+                current_dir,
+                vec![input, output, answer],
+                Stdio::null(),
+                message.try_clone()?,
+                message,
+                Duration::from_secs(10),
+            ) {
+                let checked = match (status, detail.exit_code) {
+                    (ExecuteStatus::Success, _) => CheckerStatus::Accepted,
+                    (ExecuteStatus::TimeLimitExceed, _) => {
+                        CheckerStatus::Failed("checker timed out".to_string())
+                    }
+                    (_, None) => {
+                        CheckerStatus::Failed("checker was killed by a signal".to_string())
+                    }
+                    (_, Some(TESTLIB_FAIL)) => {
+                        CheckerStatus::Failed(format!("checker exited with FAIL ({TESTLIB_FAIL})"))
+                    }
+                    (_, Some(_)) => CheckerStatus::Rejected,
+                };
+                Ok((checked, detail.exit_code))
+            } else {
+                bail!("failed to run")
+            }
+        }
+        CheckerProtocol::Icpc => {
+            // input answer feedback_dir < output
+            // 前回の実行の judgemessage.txt などが残らないよう，空のディレクトリを作り直す
+            let feedback_dir = message_path.with_extension("feedback");
+            if feedback_dir.exists() {
+                remove_dir_all(&feedback_dir)?;
+            }
+            create_dir_all(&feedback_dir)?;
+            let feedback = feedback_dir.canonicalize()?.to_string_lossy().to_string();
+
+            if let Ok((status, detail)) = run.execute_with_detail(
+                current_dir,
+                vec![input, answer, feedback],
+                File::open(&output)?,
+                message.try_clone()?,
+                message,
+                Duration::from_secs(10),
+            ) {
+                // judgemessage.txt を checker メッセージの先頭に置く
+                let judge_message = feedback_dir.join("judgemessage.txt");
+                if judge_message.exists() {
+                    let content = read_to_string(&judge_message)? + &read_to_string(message_path)?;
+                    write(message_path, content)?;
+                }
+
+                let checked = match (status, detail.exit_code) {
+                    (ExecuteStatus::TimeLimitExceed, _) => {
+                        CheckerStatus::Failed("checker timed out".to_string())
+                    }
+                    (_, Some(ICPC_ACCEPTED)) => CheckerStatus::Accepted,
+                    (_, Some(ICPC_WRONG_ANSWER)) => CheckerStatus::Rejected,
+                    (_, Some(code)) => {
+                        CheckerStatus::Failed(format!("checker exited with unexpected code {code}"))
+                    }
+                    (_, None) => {
+                        CheckerStatus::Failed("checker was killed by a signal".to_string())
+                    }
+                };
+                Ok((checked, detail.exit_code))
+            } else {
+                bail!("failed to run")
+            }
+        }
+    }
+}
+
+/// --show-diff で表示する差分の最大行数
+const DIFF_LIMIT: usize = 3;
+
+/// output と answer の異なる行を先頭から最大 limit 行列挙する
+fn first_differences(output: &str, answer: &str, limit: usize) -> Vec<String> {
+    let mut output_lines = output.lines();
+    let mut answer_lines = answer.lines();
+
+    let mut diff = Vec::new();
+    let mut line = 1;
+    while diff.len() < limit {
+        match (output_lines.next(), answer_lines.next()) {
+            (None, None) => break,
+            (found, expected) if found != expected => diff.push(format!(
+                "line {line}: expected {}, found {}",
+                expected.map_or("<EOF>".to_string(), |s| format!("{s:?}")),
+                found.map_or("<EOF>".to_string(), |s| format!("{s:?}")),
+            )),
+            _ => {}
+        }
+        line += 1;
+    }
+    diff
+}
+
+fn diff_lines(info: &JudgeInfo, limit: usize) -> Result<Vec<String>> {
+    let output = read_to_string(info.get_output_path().unwrap())?;
+    let answer = read_to_string(info.get_answer_path().unwrap())?;
+    Ok(first_differences(&output, &answer, limit))
+}
+
+/// checker メッセージの先頭行を返す
+fn read_first_line(path: &Path) -> Option<String> {
+    let content = read_to_string(path).ok()?;
+    let line = content.lines().next()?.trim();
+    if line.is_empty() {
+        None
     } else {
-        bail!("failed to run")
+        Some(line.to_string())
     }
 }
 
-fn judge_root<P: AsRef<Path>>(
-    solver: &Path,
-    checker_dir: P,
+/// 1 ケースを実行してジャッジする
+/// gzip 圧縮された入力は一時ディレクトリに展開してから実行と判定を行い，結果には元の入力のパスを残す
+fn judge_case<P: AsRef<Path>, Q: AsRef<Path>>(
+    rundir: P,
+    runstep: &CommandStep,
+    checker_dir: Q,
     checker_step: &Option<CommandStep>,
-    testcases: &Vec<JudgeInfo>,
-    langs: &Vec<Box<dyn Language>>,
+    target: JudgeInfo,
     outdir: &Path,
-    timelimit: f64,
-    policy: JudgePolicy,
-) -> Result<()> {
-    let outdir = outdir.join(solver.file_stem().unwrap().to_str().unwrap());
+    args: &JudgeArgs,
+) -> Result<CaseResult> {
+    let input_path = target.get_input_path().unwrap().clone();
+    // 展開先の一時ディレクトリは圧縮された入力の場合のみ作る
+    let inflate_dir = if is_gzip(&input_path) {
+        Some(TempDir::new()?)
+    } else {
+        None
+    };
+    let target = match inflate_dir {
+        Some(ref dir) => target.input(&inflate_input(&input_path, dir.path())?),
+        None => target,
+    };
+
+    let mut result = judge_inflated_case(
+        rundir,
+        runstep,
+        checker_dir,
+        checker_step,
+        target,
+        outdir,
+        args,
+    )?;
+    result.info = result.info.input(&input_path);
+    Ok(result)
+}
+
+fn judge_inflated_case<P: AsRef<Path>, Q: AsRef<Path>>(
+    rundir: P,
+    runstep: &CommandStep,
+    checker_dir: Q,
+    checker_step: &Option<CommandStep>,
+    target: JudgeInfo,
+    outdir: &Path,
+    args: &JudgeArgs,
+) -> Result<CaseResult> {
+    let timelimit = target.timelimit.unwrap_or(args.timelimit);
+    let outdir = &outdir.join(&target.subdir);
     if !outdir.exists() {
-        create_dir_all(&outdir)?;
+        create_dir_all(outdir)?;
+    }
+
+    // --runs 回実行し，実行時間等は最大値を採用する
+    // 途中で Success 以外になった場合はその時点で打ち切る
+    let mut runs = Vec::new();
+    let mut solved: Option<JudgeInfo> = None;
+    for _ in 0..args.runs {
+        let info = solve(
+            &rundir,
+            target.clone(),
+            outdir,
+            runstep,
+            timelimit,
+            args.save_stderr,
+        )?;
+        runs.push(info.elapsed.unwrap());
+
+        let success = info.status == Some(ExecuteStatus::Success);
+        solved = Some(match solved {
+            Some(prev) => {
+                let cpu_time = info.cpu_time.max(prev.cpu_time).unwrap();
+                let memory = info.memory.max(prev.memory).unwrap();
+                info.cpu_time(cpu_time).memory(memory)
+            }
+            None => info,
+        });
+        if !success {
+            break;
+        }
+    }
+    let max_elapsed = *runs.iter().max().unwrap();
+    let target = solved.unwrap().elapsed(max_elapsed).runs(runs);
+    info!(
+        "[OUTPUT] {:?}, status = {:?}",
+        target.get_output_path(),
+        target.status
+    );
+
+    let status = target.status.unwrap();
+    match status {
+        ExecuteStatus::Success => {
+            let name = input_name(target.get_input_path().unwrap());
+            let message_path = outdir.join(format!("{name}.chk"));
+            let (status, exit_code) = if let Some(ref runstep) = checker_step {
+                judge(
+                    &checker_dir,
+                    &target,
+                    runstep,
+                    &message_path,
+                    args.checker_protocol,
+                )?
+            } else {
+                let status = if judge_by_diff(&checker_dir, &target)? {
+                    CheckerStatus::Accepted
+                } else {
+                    CheckerStatus::Rejected
+                };
+                (status, None)
+            };
+            info!("[JUDGE] {:#?}, status = {:?}", target, status);
+
+            // --verdict-map による判定の上書き（チェッカー自体の失敗は JE のまま残す）
+            let checker_failed = matches!(status, CheckerStatus::Failed(_));
+            if checker_step.is_some() && !checker_failed && !args.verdict_map.is_empty() {
+                let message = read_to_string(&message_path).unwrap_or_default();
+                if let Some(rule) = args
+                    .verdict_map
+                    .iter()
+                    .find(|rule| rule.matches(exit_code, &message))
+                {
+                    return Ok(CaseResult::new(rule.verdict.clone(), target)
+                        .message(read_first_line(&message_path))
+                        .score(rule.score));
+                }
+            }
+
+            match status {
+                CheckerStatus::Accepted => {
+                    let near_timelimit = args.near_timelimit.is_some_and(|margin| {
+                        is_near_timelimit(target.elapsed.unwrap(), timelimit, margin)
+                    });
+                    Ok(CaseResult::new(Verdict::Accepted, target).near_timelimit(near_timelimit))
+                }
+                CheckerStatus::Rejected => {
+                    let message = checker_step
+                        .as_ref()
+                        .and_then(|_| read_first_line(&message_path));
+                    let diff = if args.show_diff {
+                        diff_lines(&target, DIFF_LIMIT)?
+                    } else {
+                        Vec::new()
+                    };
+                    Ok(CaseResult::new(Verdict::WrongAnswer, target)
+                        .message(message)
+                        .diff(diff))
+                }
+                CheckerStatus::Failed(reason) => {
+                    warn!("[JE] {:?}: {reason}", target.get_input_path().unwrap());
+                    let message = match read_first_line(&message_path) {
+                        Some(line) => format!("{reason}\n{line}"),
+                        None => reason,
+                    };
+                    Ok(CaseResult::new(Verdict::JudgeError, target).message(Some(message)))
+                }
+            }
+        }
+        ExecuteStatus::TimeLimitExceed => Ok(CaseResult::new(Verdict::TimeLimitExceed, target)),
+        ExecuteStatus::OutputLimitExceed => Ok(CaseResult::new(Verdict::OutputLimitExceed, target)),
+        ExecuteStatus::Fail => Ok(CaseResult::new(Verdict::RuntimeError, target)),
+    }
+}
+
+/// コンパイル済みの solver
+struct CompiledSolver {
+    rundir: TempDir,
+    /// 実行した compile のコマンド列（セッションログに記録する）
+    compile_steps: Vec<CommandStep>,
+    runstep: CommandStep,
+}
+
+/// solver ごとの出力先ディレクトリ
+fn solver_outdir(outdir: &Path, solver: &Path) -> PathBuf {
+    outdir.join(solver.file_stem().unwrap().to_str().unwrap())
+}
+
+/// 全 solver をジャッジ前にコンパイルする
+/// コンパイラの出力は outdir/<solver>/compile.log に保存され，CE の solver は None となる
+fn compile_solvers(
+    solvers: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+    args: &JudgeArgs,
+) -> Result<Vec<Option<CompiledSolver>>> {
+    #[derive(Tabled)]
+    struct CompileStatus {
+        solver: String,
+        status: String,
+        compiler_output: String,
     }
 
-    let mut testcases = testcases.clone();
+    let mut compiled = Vec::new();
+    let mut rows = Vec::new();
+    for solver in solvers {
+        let outdir = solver_outdir(&args.outdir, solver);
+        if !outdir.exists() {
+            create_dir_all(&outdir)?;
+        }
+        let log = outdir.join("compile.log");
 
-    // generate outputs
-    let rundir = TempDir::new()?;
-    let runstep = compile_and_get_runstep(&rundir, &solver, &langs)?;
-    let bar = ProgressBar::new(testcases.len() as u64);
+        let rundir = TempDir::new()?;
+        let (status, solver_compiled) =
+            match compile_and_get_steps_with_log(&rundir, solver, &args.grader, langs, &log) {
+                Ok((compile_steps, runstep)) => {
+                    let runstep = runstep.output_limit(args.output_limit * 1024 * 1024);
+                    (
+                        style("OK").green().to_string(),
+                        Some(CompiledSolver {
+                            rundir,
+                            compile_steps,
+                            runstep,
+                        }),
+                    )
+                }
+                Err(e) => {
+                    warn!("[CE] {solver:?}: {e}");
+                    (Verdict::CompileError.styled(), None)
+                }
+            };
+
+        let output = read_to_string(&log).unwrap_or_default();
+        let mut lines: Vec<_> = output
+            .lines()
+            .take(COMPILE_LOG_LIMIT)
+            .map(String::from)
+            .collect();
+        if solver_compiled.is_none() {
+            lines.push(format!("{log:?}"));
+        }
+        rows.push(CompileStatus {
+            solver: format!("{solver:?}"),
+            status,
+            compiler_output: lines.join("\n"),
+        });
+        compiled.push(solver_compiled);
+    }
+
+    println!("{}", TableFormat::new(args).render(Table::new(rows)));
+    Ok(compiled)
+}
+
+/// セッションログに記録する，cwd で実行した compile のコマンド列と実行コマンド
+/// cwd が一時ディレクトリの場合はジャッジ終了後に削除されるため，compile から順に実行すれば再現できる
+fn executed_commands(cwd: &Path, compile_steps: &[CommandStep], run: String) -> Vec<String> {
+    let mut commands = vec![format!("cwd = {cwd:?}")];
+    commands.extend(compile_steps.iter().map(|step| format!("compile: {step}")));
+    commands.push(format!("run: {run}"));
+    commands
+}
+
+/// コンパイル状況の表に表示するコンパイラ出力の最大行数
+const COMPILE_LOG_LIMIT: usize = 5;
+
+fn judge_root<P: AsRef<Path>>(
+    solver: &Path,
+    compiled: &CompiledSolver,
+    checker_dir: P,
+    checker_step: &Option<CommandStep>,
+    testcases: &[JudgeInfo],
+    args: &JudgeArgs,
+    multi: &MultiProgress,
+) -> Result<Vec<CaseResult>> {
+    let outdir = solver_outdir(&args.outdir, solver);
+    let rundir = compiled.rundir.path();
+    let runstep = &compiled.runstep;
+
+    let mut results = Vec::new();
+    let mut stopped = false;
+    let mut failures = 0;
+    let bar = multi.add(ProgressBar::new(testcases.len() as u64));
     bar.set_style(
         ProgressStyle::default_bar()
-            .template(&format!("[SOLVE {solver:?}] {{bar}} {{pos:>4}}/{{len:4}}"))?,
+            .template(&format!("[JUDGE {solver:?}] {{bar}} {{pos:>4}}/{{len:4}}"))?,
     );
-    for target in testcases.iter_mut() {
-        match solve(
-            &rundir,
-            target.get_input_path().unwrap(),
+    for target in testcases.iter() {
+        if stopped {
+            results.push(CaseResult::new(Verdict::Skipped, target.clone()));
+            bar.inc(1);
+            continue;
+        }
+
+        match judge_case(
+            rundir,
+            runstep,
+            &checker_dir,
+            checker_step,
+            target.clone(),
             &outdir,
-            &runstep,
-            timelimit,
+            args,
         ) {
-            Ok((status, output)) => {
-                info!("[OUTPUT] {:?}, status = {:?}", output, status);
-
-                *target = target.clone().output(&output).status(status);
+            Ok(result) => {
+                if result.verdict != Verdict::Accepted {
+                    failures += 1;
+                }
+                stopped = args.policy.should_break(&result.verdict)
+                    || args.max_failures.is_some_and(|max| failures >= max);
 
-                if policy == JudgePolicy::TLEBreak && status == ExecuteStatus::TimeLimitExceed {
-                    break;
+                if args.live || result.verdict != Verdict::Accepted {
+                    bar.println(format!(
+                        "[{}] {} {:?} {}",
+                        solver.file_stem().unwrap().to_string_lossy(),
+                        result.verdict.styled(),
+                        result.info.get_input_path().unwrap(),
+                        result.describe().replace('\n', " ")
+                    ));
                 }
+                results.push(result);
             }
             Err(err) => {
                 warn!("[IGNORE] {:?}, reason = {:?}", target, err);
+                results.push(CaseResult::new(Verdict::Skipped, target.clone()));
             }
         }
         bar.inc(1);
     }
     bar.finish();
 
+    Ok(results)
+}
+
+/// 結果表の整形方法
+struct TableFormat {
+    style: TableStyle,
+    max_width: Option<usize>,
+    /// パスを相対表示する際の基準ディレクトリ（相対表示しない場合は空）
+    roots: Vec<PathBuf>,
+}
+
+impl TableFormat {
+    fn new(args: &JudgeArgs) -> Self {
+        let roots = if args.relative_paths {
+            args.testcases
+                .iter()
+                .chain(args.answer_dir.iter())
+                .cloned()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            style: args.table_style,
+            max_width: args.max_width,
+            roots,
+        }
+    }
+
+    /// 表に表示するパス
+    fn path(&self, path: &Path) -> String {
+        match self
+            .roots
+            .iter()
+            .find_map(|root| path.strip_prefix(root).ok())
+        {
+            Some(relative) => format!("{relative:?}"),
+            None => format!("{path:?}"),
+        }
+    }
+
+    fn render(&self, mut table: Table) -> String {
+        if let Some(width) = self.max_width {
+            // 複数行のセルは行ごとに切り詰める
+            table.modify(
+                Segment::all(),
+                Format::content(|s| {
+                    s.lines()
+                        .map(|line| truncate_str(line, width, "...").to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }),
+            );
+        }
+        match self.style {
+            TableStyle::Ascii => table.with(Style::ascii()),
+            TableStyle::Markdown => table
+                .with(Style::markdown())
+                .modify(Segment::all(), Format::content(|s| s.replace('\n', "<br>"))),
+            TableStyle::Compact => table.with(Style::psql()),
+        };
+        table.to_string()
+    }
+}
+
+/// ケースごとの結果表を出力する
+fn print_results(results: &[CaseResult], format: &TableFormat) {
     #[derive(Tabled)]
     struct Result {
         status: String,
         input_and_answer: String,
         info: String,
     }
-    let mut results = Vec::new();
 
-    // judge
-    let bar = ProgressBar::new(testcases.len() as u64);
-    bar.set_style(
-        ProgressStyle::default_bar()
-            .template(&format!("[JUDGE {solver:?}] {{bar}} {{pos:>4}}/{{len:4}}"))?,
+    let mut rows = Vec::new();
+    for result in results {
+        let target = &result.info;
+        let info = result.describe();
+
+        rows.push(Result {
+            status: result.verdict.styled(),
+            input_and_answer: format!(
+                "{}\n{}",
+                format.path(target.get_input_path().unwrap()),
+                format.path(target.get_answer_path().unwrap())
+            ),
+            info,
+        });
+    }
+
+    println!("{}", format.render(Table::new(rows)));
+}
+
+/// 行をテストケース，列を solver とした比較表を出力する
+/// 全 solver の results は同じテストケース順に並んでいる
+fn print_matrix(solvers: &[PathBuf], all_results: &[Vec<CaseResult>], format: &TableFormat) {
+    let mut builder = Builder::default();
+
+    let mut header = vec!["testcase".to_string()];
+    header.extend(
+        solvers
+            .iter()
+            .map(|solver| solver.file_stem().unwrap().to_string_lossy().to_string()),
     );
-    for target in testcases.iter() {
-        match target.status {
-            Some(ExecuteStatus::Success) => {
-                // ジャッジ
-                let timer = Instant::now();
-                let status = if let Some(ref runstep) = checker_step {
-                    judge(&checker_dir, target, runstep)
-                } else {
-                    judge_by_diff(&checker_dir, target)
-                };
-                let elapsed = timer.elapsed();
-
-                // 結果の作成
-                match status {
-                    Ok(status) => {
-                        info!("[JUDGE] {:#?}, status = {:?}", target, status);
-
-                        let result = if status {
-                            Result {
-                                status: "AC".to_string(),
-                                input_and_answer: format!(
-                                    "{:?}\n{:?}",
-                                    target.get_input_path().unwrap(),
-                                    target.get_answer_path().unwrap()
-                                ),
-                                info: format!("time = {elapsed:?}"),
-                            }
-                        } else {
-                            Result {
-                                status: "WA".to_string(),
-                                input_and_answer: format!(
-                                    "{:?}\n{:?}",
-                                    target.get_input_path().unwrap(),
-                                    target.get_answer_path().unwrap()
-                                ),
-                                info: format!("{:?}", target.get_output_path().unwrap()),
-                            }
-                        };
-                        results.push(result);
-                    }
-                    Err(err) => {
-                        warn!("[JUDGE] {:?}, reason = {:?}", target, err);
-                    }
-                }
-            }
-            Some(status) => {
-                results.push(Result {
-                    status: status.to_string(),
-                    input_and_answer: format!(
-                        "{:?}\n{:?}",
-                        target.get_input_path().unwrap(),
-                        target.get_answer_path().unwrap()
-                    ),
-                    info: "".to_string(),
-                });
-            }
-            None => {
-                results.push(Result {
-                    status: "SKIP".to_string(),
-                    input_and_answer: format!(
-                        "{:?}\n{:?}",
-                        target.get_input_path().unwrap(),
-                        target.get_answer_path().unwrap()
-                    ),
-                    info: "".to_string(),
-                });
-            }
+    builder.push_record(header);
+
+    let count = all_results.first().map_or(0, |results| results.len());
+    for i in 0..count {
+        let input = all_results[0][i].info.get_input_path().unwrap();
+        let mut row = vec![format.path(input)];
+        for results in all_results {
+            let result = &results[i];
+            let cell = match (&result.verdict, result.info.elapsed) {
+                (Verdict::Skipped, _) | (_, None) => result.verdict.styled(),
+                (_, Some(elapsed)) => format!("{} {elapsed:.2?}", result.verdict.styled()),
+            };
+            row.push(cell);
         }
-        bar.inc(1);
+        builder.push_record(row);
     }
-    bar.finish();
 
-    println!("{}", Table::new(results));
+    println!("{}", format.render(builder.build()));
+}
+
+/// solver ごとの集計表を出力する
+fn print_summary(solver: &Path, results: &[CaseResult], format: &TableFormat) {
+    let summary = JudgeSummary::new(results);
 
-    Ok(())
+    #[derive(Tabled)]
+    struct Summary {
+        solver: String,
+        #[tabled(rename = "AC")]
+        accepted: usize,
+        #[tabled(rename = "WA")]
+        wrong_answer: usize,
+        #[tabled(rename = "TLE")]
+        time_limit_exceed: usize,
+        #[tabled(rename = "RE")]
+        runtime_error: usize,
+        #[tabled(rename = "SKIP")]
+        skipped: usize,
+        other: usize,
+        slowest: String,
+        result: String,
+    }
+
+    let slowest = match summary.slowest {
+        Some((ref path, elapsed)) => format!("{}\ntime = {elapsed:?}", format.path(path)),
+        None => "".to_string(),
+    };
+    let row = Summary {
+        solver: format!("{solver:?}"),
+        accepted: summary.count(Verdict::Accepted),
+        wrong_answer: summary.count(Verdict::WrongAnswer),
+        time_limit_exceed: summary.count(Verdict::TimeLimitExceed),
+        runtime_error: summary.count(Verdict::RuntimeError),
+        skipped: summary.count(Verdict::Skipped),
+        other: summary.count_other(),
+        slowest,
+        result: if summary.passed() {
+            style("PASS").green().to_string()
+        } else {
+            style("FAIL").red().to_string()
+        },
+    };
+
+    println!("{}", format.render(Table::new(vec![row])));
 }
 
 pub(super) fn root(args: JudgeArgs) -> Result<()> {
@@ -369,14 +1512,14 @@ pub(super) fn root(args: JudgeArgs) -> Result<()> {
 
     let solvers = {
         let mut solvers = Vec::new();
-        for base in args.solvers {
-            for file in find_files(&base, args.recursive)? {
+        for base in &args.solvers {
+            for file in find_files(base, args.recursive)? {
                 solvers.push(file);
             }
         }
         solvers
     };
-    if solvers.len() == 0 {
+    if solvers.is_empty() {
         println!("no solver found!");
         return Ok(());
     }
@@ -384,48 +1527,242 @@ pub(super) fn root(args: JudgeArgs) -> Result<()> {
 
     let testcases = {
         let mut all_cases = Vec::new();
-        for base in args.testcases {
-            let mut files = find_files(&base, true)?;
+        for base in &args.testcases {
+            let mut files = find_files(base, true)?;
             all_cases.append(&mut files);
         }
-        enumerate_valid_testcases(&all_cases)
+        let filters = args
+            .filter
+            .iter()
+            .map(|pattern| glob_to_regex(pattern))
+            .collect::<Result<Vec<_>>>()?;
+        let excludes = args
+            .exclude
+            .iter()
+            .map(|pattern| glob_to_regex(pattern))
+            .collect::<Result<Vec<_>>>()?;
+
+        let valid_cases = match args.answer_dir {
+            Some(ref answer_dir) => {
+                ensure!(
+                    answer_dir.is_dir(),
+                    "answer directory {answer_dir:?} not found"
+                );
+                enumerate_testcases_with_answer_dir(&args.testcases, answer_dir)?
+            }
+            None => enumerate_valid_testcases(&all_cases),
+        };
+
+        let mut testcases = Vec::new();
+        for case in valid_cases {
+            let name = input_name(case.get_input_path().unwrap());
+            if !is_selected(&name, &filters, &excludes) {
+                continue;
+            }
+            if !args.tag.is_empty() {
+                let tags = read_tags(case.get_input_path().unwrap())?;
+                if !tags.iter().any(|tag| args.tag.contains(tag)) {
+                    continue;
+                }
+            }
+
+            let alt_answers = find_alt_answers(case.get_answer_path().unwrap());
+            let case = case.alt_answers(alt_answers);
+
+            match read_timelimit(case.get_input_path().unwrap())? {
+                Some(timelimit) => testcases.push(case.timelimit(timelimit)),
+                None => testcases.push(case),
+            }
+        }
+        let mut testcases = assign_subdirs(testcases);
+        if let Some(seed) = args.shuffle {
+            let seed = seed.unwrap_or_else(|| SplitMix64::from_time().next_u64());
+            // 同じ順序を再現できるよう seed を表示する
+            println!("shuffle seed = {seed}");
+            shuffle(&mut testcases, seed);
+        }
+        testcases
     };
-    if testcases.len() == 0 {
+    if testcases.is_empty() {
         println!("no testcase found!");
         return Ok(());
     }
 
     let langs = make_languages(&args.language)?;
 
+    for grader in &args.grader {
+        ensure!(grader.exists(), "grader {grader:?} not found");
+    }
+
     if !args.outdir.exists() {
         create_dir_all(&args.outdir)?;
     }
 
     let checker_dir = TempDir::new()?;
-    let checker_step = if let Some(checker) = args.checker {
-        ensure!(checker.exists(), "checker {checker:?} not found");
+    let (checker_cwd, checker_compile_steps, checker_step) =
+        if let Some(ref compare_cmd) = args.compare_cmd {
+            // 比較コマンドはカレントディレクトリで sh -c により実行する
+            let step = CommandStep::new(
+                "sh".to_string(),
+                vec![
+                    "-c".to_string(),
+                    format!("{compare_cmd} \"$@\""),
+                    "sh".to_string(),
+                ],
+            );
+            (current_dir()?, Vec::new(), Some(step))
+        } else if let Some(ref checker) = args.checker {
+            ensure!(checker.exists(), "checker {checker:?} not found");
 
-        Some(compile_and_get_runstep(&checker_dir, &checker, &langs)?)
-    } else {
-        None
+            let (compile_steps, step) = compile_and_get_steps(&checker_dir, checker, &[], &langs)?;
+            (checker_dir.path().to_path_buf(), compile_steps, Some(step))
+        } else {
+            (checker_dir.path().to_path_buf(), Vec::new(), None)
+        };
+
+    let format = TableFormat::new(&args);
+    let compiled = compile_solvers(&solvers, &langs, &args)?;
+    println!();
+
+    // CE の solver は全ケースを CE とする
+    let compile_error = || {
+        testcases
+            .iter()
+            .map(|case| CaseResult::new(Verdict::CompileError, case.clone()))
+            .collect::<Vec<_>>()
     };
 
-    for (i, solver) in solvers.iter().enumerate() {
-        judge_root(
-            &solver,
-            &checker_dir,
-            &checker_step,
-            &testcases,
-            &langs,
-            &args.outdir,
-            args.timelimit,
-            args.policy,
-        )?;
+    let multi = MultiProgress::new();
+    let all_results = if args.parallel {
+        // 全 solver を並行にジャッジし，終了後に solver 順に結果を出力する
+        let all_results = thread::scope(|scope| {
+            let handles: Vec<_> = solvers
+                .iter()
+                .zip(&compiled)
+                .map(|(solver, compiled)| {
+                    scope.spawn(|| match compiled {
+                        Some(compiled) => judge_root(
+                            solver,
+                            compiled,
+                            &checker_cwd,
+                            &checker_step,
+                            &testcases,
+                            &args,
+                            &multi,
+                        ),
+                        None => Ok(compile_error()),
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>>>()
+        })?;
 
-        if i + 1 < solvers.len() {
-            println!("");
+        for (i, ((solver, compiled), results)) in
+            solvers.iter().zip(&compiled).zip(&all_results).enumerate()
+        {
+            if i > 0 {
+                println!();
+            }
+            if compiled.is_some() {
+                print_results(results, &format);
+            }
+            print_summary(solver, results, &format);
         }
+        all_results
+    } else {
+        let mut all_results = Vec::new();
+        for (i, (solver, compiled)) in solvers.iter().zip(&compiled).enumerate() {
+            if i > 0 {
+                println!();
+            }
+            let results = match compiled {
+                Some(compiled) => {
+                    let results = judge_root(
+                        solver,
+                        compiled,
+                        &checker_cwd,
+                        &checker_step,
+                        &testcases,
+                        &args,
+                        &multi,
+                    )?;
+                    print_results(&results, &format);
+                    results
+                }
+                None => compile_error(),
+            };
+            print_summary(solver, &results, &format);
+            all_results.push(results);
+        }
+        all_results
+    };
+
+    if solvers.len() > 1 {
+        println!();
+        print_matrix(&solvers, &all_results, &format);
+    }
+
+    if let Some(ref path) = args.junit {
+        report::write_junit(path, &solvers, &all_results)?;
+        info!("[JUNIT] {path:?}");
     }
+    if let Some(ref path) = args.html {
+        report::write_html(path, &solvers, &all_results)?;
+        info!("[HTML] {path:?}");
+    }
+
+    // 再現用に，実際に実行したコンパイル・実行コマンドとその実行ディレクトリを記録する
+    let checker_commands = match checker_step {
+        Some(ref step) => {
+            let run = match args.checker_protocol {
+                CheckerProtocol::Testlib => format!("{step} <input> <output> <answer>"),
+                CheckerProtocol::Icpc => {
+                    format!("{step} <input> <answer> <feedback_dir> < <output>")
+                }
+            };
+            executed_commands(&checker_cwd, &checker_compile_steps, run)
+        }
+        None => vec!["compare: built-in diff".to_string()],
+    };
+    let commands: Vec<_> = solvers
+        .iter()
+        .zip(&compiled)
+        .map(|(solver, compiled)| match compiled {
+            Some(compiled) => executed_commands(
+                compiled.rundir.path(),
+                &compiled.compile_steps,
+                format!("{} < <input> > <output>", compiled.runstep),
+            ),
+            None => vec![format!(
+                "error: compile error (see {:?})",
+                solver_outdir(&args.outdir, solver).join("compile.log")
+            )],
+        })
+        .collect();
+    let session_log = args.outdir.join("session.log");
+    report::write_session_log(
+        &session_log,
+        &args,
+        &checker_commands,
+        &solvers,
+        &commands,
+        &all_results,
+    )?;
+    info!("[SESSION LOG] {session_log:?}");
+
+    // checker の不具合は solver の誤りとして扱わず，エラーとして終了する
+    let judge_errors = all_results
+        .iter()
+        .flatten()
+        .filter(|result| result.verdict == Verdict::JudgeError)
+        .count();
+    ensure!(
+        judge_errors == 0,
+        "checker failed on {judge_errors} case(s)"
+    );
 
     Ok(())
 }
@@ -434,9 +1771,153 @@ pub(super) fn root(args: JudgeArgs) -> Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_first_differences() {
+        assert!(first_differences("1\n2\n", "1\n2\n", 3).is_empty());
+        assert_eq!(
+            first_differences("1\n3\n", "1\n2\n", 3),
+            vec!["line 2: expected \"2\", found \"3\"".to_string()]
+        );
+        assert_eq!(
+            first_differences("1\n", "1\n2\n", 3),
+            vec!["line 2: expected \"2\", found <EOF>".to_string()]
+        );
+        assert_eq!(
+            first_differences("a\nb\nc\nd\n", "w\nx\ny\nz\n", 2).len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_timing_stats() {
+        assert_eq!(timing_stats(&[]), None);
+        assert_eq!(
+            timing_stats(&[
+                Duration::from_millis(30),
+                Duration::from_millis(10),
+                Duration::from_millis(20)
+            ]),
+            Some((
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(30)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_is_near_timelimit() {
+        assert!(is_near_timelimit(Duration::from_millis(1900), 2.0, 20.0));
+        assert!(is_near_timelimit(Duration::from_millis(1600), 2.0, 20.0));
+        assert!(!is_near_timelimit(Duration::from_millis(1500), 2.0, 20.0));
+        assert!(!is_near_timelimit(Duration::from_millis(1900), 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_table_format() {
+        let format = TableFormat {
+            style: TableStyle::Ascii,
+            max_width: Some(8),
+            roots: vec![PathBuf::from("./testcases")],
+        };
+        assert_eq!(
+            format.path(Path::new("./testcases/input/a.in")),
+            "\"input/a.in\""
+        );
+        assert_eq!(format.path(Path::new("other/a.in")), "\"other/a.in\"");
+
+        let mut builder = Builder::default();
+        builder.push_record(["abcdefghijkl\nabc"]);
+        let rendered = format.render(builder.build());
+        assert!(rendered.contains("abcde..."));
+        assert!(rendered.contains("abc "));
+
+        let format = TableFormat {
+            style: TableStyle::Markdown,
+            max_width: None,
+            roots: Vec::new(),
+        };
+        let mut builder = Builder::default();
+        builder.push_record(["header"]);
+        builder.push_record(["a\nb"]);
+        assert!(format.render(builder.build()).contains("a<br>b"));
+    }
+
+    #[test]
+    fn test_format_memory() {
+        assert_eq!(format_memory(512), "512 KiB");
+        assert_eq!(format_memory(1024), "1.0 MiB");
+        assert_eq!(format_memory(262144), "256.0 MiB");
+    }
+
+    #[test]
+    fn test_judge_policy() {
+        assert!(!JudgePolicy::All.should_break(&Verdict::TimeLimitExceed));
+        assert!(JudgePolicy::TLEBreak.should_break(&Verdict::TimeLimitExceed));
+        assert!(!JudgePolicy::TLEBreak.should_break(&Verdict::WrongAnswer));
+        assert!(JudgePolicy::WABreak.should_break(&Verdict::WrongAnswer));
+        assert!(JudgePolicy::WABreak.should_break(&Verdict::RuntimeError));
+        assert!(!JudgePolicy::WABreak.should_break(&Verdict::Accepted));
+    }
+
+    #[test]
+    fn test_parse_verdict_rule() {
+        let rule = parse_verdict_rule("7=PC:50").unwrap();
+        assert_eq!(rule.key, VerdictKey::ExitCode(7));
+        assert_eq!(rule.verdict, Verdict::Custom("PC".to_string()));
+        assert_eq!(rule.score, Some(50.0));
+        assert!(rule.matches(Some(7), ""));
+        assert!(!rule.matches(Some(1), ""));
+
+        let rule = parse_verdict_rule("points=ok=AC").unwrap();
+        assert_eq!(rule.key, VerdictKey::Keyword("points=ok".to_string()));
+        assert_eq!(rule.verdict, Verdict::Accepted);
+        assert_eq!(rule.score, None);
+        assert!(rule.matches(None, "partial points=ok"));
+
+        assert!(parse_verdict_rule("PC").is_err());
+        assert!(parse_verdict_rule("=PC").is_err());
+        assert!(parse_verdict_rule("7=").is_err());
+        assert!(parse_verdict_rule("7=PC:x").is_err());
+    }
+
+    #[test]
+    fn test_judge_summary() {
+        let info = |name: &str, millis: u64| {
+            JudgeInfo::new()
+                .input(Path::new(name))
+                .elapsed(Duration::from_millis(millis))
+        };
+
+        let summary = JudgeSummary::new(&[
+            CaseResult::new(Verdict::Accepted, info("a.in", 10)),
+            CaseResult::new(Verdict::WrongAnswer, info("b.in", 30)),
+            CaseResult::new(Verdict::Accepted, info("c.in", 20)),
+            CaseResult::new(Verdict::Skipped, JudgeInfo::new()),
+            CaseResult::new(Verdict::JudgeError, info("d.in", 5)),
+            CaseResult::new(Verdict::from("PC"), info("e.in", 5)),
+        ]);
+        assert_eq!(summary.count(Verdict::Accepted), 2);
+        assert_eq!(summary.count_other(), 2);
+        assert_eq!(summary.count(Verdict::WrongAnswer), 1);
+        assert_eq!(summary.count(Verdict::TimeLimitExceed), 0);
+        assert_eq!(summary.count(Verdict::Skipped), 1);
+        assert_eq!(
+            summary.slowest,
+            Some((PathBuf::from("b.in"), Duration::from_millis(30)))
+        );
+        assert!(!summary.passed());
+
+        let summary = JudgeSummary::new(&[CaseResult::new(Verdict::Accepted, info("a.in", 10))]);
+        assert!(summary.passed());
+
+        let summary = JudgeSummary::new(&[]);
+        assert!(!summary.passed());
+    }
+
     #[test]
     fn test_enumerate_valid_testcases() {
-        let cases = enumerate_valid_testcases(&vec![
+        let cases = enumerate_valid_testcases(&[
             PathBuf::from("input/test.in"),
             PathBuf::from("answer/test.ans"),
         ]);
@@ -450,11 +1931,91 @@ mod tests {
             Some(&PathBuf::from("answer/test.ans"))
         );
 
-        let cases = enumerate_valid_testcases(&vec![
+        let cases = enumerate_valid_testcases(&[
             PathBuf::from("input/test.in"),
             PathBuf::from("answer/invalid.ans"),
         ]);
         assert_eq!(cases.len(), 0);
+
+        // 異なるサブディレクトリの同名のケースはそれぞれのサブディレクトリの .ans と組になる
+        let cases = enumerate_valid_testcases(&[
+            PathBuf::from("input/subtask1/a.in"),
+            PathBuf::from("input/subtask2/a.in"),
+            PathBuf::from("answer/subtask1/a.ans"),
+            PathBuf::from("answer/subtask2/a.ans"),
+        ]);
+        let pairs: Vec<_> = cases
+            .iter()
+            .map(|case| {
+                (
+                    case.get_input_path().unwrap(),
+                    case.get_answer_path().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (
+                    &PathBuf::from("input/subtask1/a.in"),
+                    &PathBuf::from("answer/subtask1/a.ans")
+                ),
+                (
+                    &PathBuf::from("input/subtask2/a.in"),
+                    &PathBuf::from("answer/subtask2/a.ans")
+                ),
+            ]
+        );
+
+        // 同じディレクトリに .in と .ans を置いた場合
+        let cases = enumerate_valid_testcases(&[
+            PathBuf::from("cases/subtask1/a.in"),
+            PathBuf::from("cases/subtask1/a.ans"),
+            PathBuf::from("cases/subtask2/a.in"),
+            PathBuf::from("cases/subtask2/a.ans"),
+        ]);
+        assert_eq!(
+            cases[1].get_answer_path(),
+            Some(&PathBuf::from("cases/subtask2/a.ans"))
+        );
+    }
+
+    #[test]
+    fn test_enumerate_testcases_with_answer_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_dir = dir.path().join("input");
+        let answer_dir = dir.path().join("answer");
+        create_dir_all(input_dir.join("sub")).unwrap();
+        create_dir_all(answer_dir.join("sub")).unwrap();
+        create_dir_all(answer_dir.join("other")).unwrap();
+
+        File::create(input_dir.join("sub/a.in")).unwrap();
+        File::create(input_dir.join("b.in")).unwrap();
+        File::create(input_dir.join("c.in")).unwrap();
+        File::create(answer_dir.join("sub/a.ans")).unwrap();
+        File::create(answer_dir.join("other/b.ans")).unwrap();
+
+        let cases =
+            enumerate_testcases_with_answer_dir(std::slice::from_ref(&input_dir), &answer_dir)
+                .unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].get_input_path(), Some(&input_dir.join("b.in")));
+        assert_eq!(
+            cases[0].get_answer_path(),
+            Some(&answer_dir.join("other/b.ans"))
+        );
+        assert_eq!(
+            cases[1].get_answer_path(),
+            Some(&answer_dir.join("sub/a.ans"))
+        );
+
+        // 同名の .ans が複数あればどちらとも組にしない
+        File::create(answer_dir.join("sub/b.ans")).unwrap();
+        let cases =
+            enumerate_testcases_with_answer_dir(std::slice::from_ref(&input_dir), &answer_dir)
+                .unwrap();
+        assert_eq!(cases.len(), 1);
+        assert_eq!(cases[0].get_input_path(), Some(&input_dir.join("sub/a.in")));
     }
 
     #[test]
@@ -478,5 +2039,149 @@ mod tests {
         assert_eq!(info.get_answer_path(), Some(&answer_path));
         assert_eq!(info.get_output_path(), None);
         assert_eq!(info.status, None);
+        assert_eq!(info.timelimit, None);
+    }
+
+    #[test]
+    fn test_read_timelimit() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("test.in");
+
+        assert_eq!(read_timelimit(&input_path).unwrap(), None);
+
+        std::fs::write(dir.path().join("test.tl"), "5.5\n").unwrap();
+        assert_eq!(read_timelimit(&input_path).unwrap(), Some(5.5));
+
+        std::fs::write(dir.path().join("test.tl"), "invalid").unwrap();
+        assert!(read_timelimit(&input_path).is_err());
+
+        std::fs::write(dir.path().join("test.tl"), "0").unwrap();
+        assert!(read_timelimit(&input_path).is_err());
+    }
+
+    #[test]
+    fn test_assign_subdirs() {
+        let cases = assign_subdirs(vec![
+            JudgeInfo::new().input(Path::new("testcases/input/subtask1/a.in")),
+            JudgeInfo::new().input(Path::new("testcases/input/subtask2/a.in")),
+            JudgeInfo::new().input(Path::new("testcases/input/b.in")),
+        ]);
+        assert_eq!(cases[0].subdir, PathBuf::from("subtask1"));
+        assert_eq!(cases[1].subdir, PathBuf::from("subtask2"));
+        assert_eq!(cases[2].subdir, PathBuf::new());
+
+        let cases = assign_subdirs(vec![JudgeInfo::new().input(Path::new("testcases/a.in"))]);
+        assert_eq!(cases[0].subdir, PathBuf::new());
+    }
+
+    #[test]
+    fn test_find_alt_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let answer = dir.path().join("test.ans");
+        File::create(&answer).unwrap();
+        assert!(find_alt_answers(&answer).is_empty());
+
+        File::create(dir.path().join("test.ans.2")).unwrap();
+        File::create(dir.path().join("test.ans.3")).unwrap();
+        File::create(dir.path().join("test.ans.5")).unwrap();
+        assert_eq!(
+            find_alt_answers(&answer),
+            vec![dir.path().join("test.ans.2"), dir.path().join("test.ans.3")]
+        );
+    }
+
+    #[test]
+    fn test_judge_by_diff_alt_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let answer = dir.path().join("test.ans");
+        let output = dir.path().join("test.out");
+        std::fs::write(&answer, "1 2\n").unwrap();
+        std::fs::write(dir.path().join("test.ans.2"), "2 1\n").unwrap();
+        std::fs::write(&output, "2 1\n").unwrap();
+
+        let info = JudgeInfo::new().answer(&answer).output(&output);
+        assert!(!judge_by_diff(dir.path(), &info).unwrap());
+
+        let info = info.alt_answers(find_alt_answers(&answer));
+        assert!(judge_by_diff(dir.path(), &info).unwrap());
+    }
+
+    #[test]
+    fn test_judge_icpc_feedback_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("test.in");
+        let answer = dir.path().join("test.ans");
+        let output = dir.path().join("test.out");
+        std::fs::write(&answer, "1\n").unwrap();
+        std::fs::write(&output, "1\n").unwrap();
+        // 入力が 1 のときのみ judgemessage.txt を書いて WA とする
+        let run = CommandStep::new(
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                "if [ \"$(cat \"$1\")\" = 1 ]; then echo wrong > \"$3/judgemessage.txt\"; exit 43; fi; exit 42".to_string(),
+                "checker".to_string(),
+            ],
+        );
+        let info = JudgeInfo::new()
+            .input(&input)
+            .answer(&answer)
+            .output(&output);
+        let message_path = dir.path().join("test.checker");
+
+        std::fs::write(&input, "1\n").unwrap();
+        let (status, _) = judge(
+            dir.path(),
+            &info,
+            &run,
+            &message_path,
+            CheckerProtocol::Icpc,
+        )
+        .unwrap();
+        assert_eq!(status, CheckerStatus::Rejected);
+        assert_eq!(read_to_string(&message_path).unwrap(), "wrong\n");
+
+        std::fs::write(&input, "2\n").unwrap();
+        let (status, _) = judge(
+            dir.path(),
+            &info,
+            &run,
+            &message_path,
+            CheckerProtocol::Icpc,
+        )
+        .unwrap();
+        assert_eq!(status, CheckerStatus::Accepted);
+        assert_eq!(read_to_string(&message_path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_read_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("test.in");
+
+        assert!(read_tags(&input_path).unwrap().is_empty());
+
+        std::fs::write(dir.path().join("test.tags"), "max, edge\nrandom\n").unwrap();
+        assert_eq!(
+            read_tags(&input_path).unwrap(),
+            vec!["max".to_string(), "edge".to_string(), "random".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_read_first_line() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let path = dir.path().join("test.chk");
+        std::fs::write(&path, "wrong answer: expected 5, found 4\nsecond line\n").unwrap();
+        assert_eq!(
+            read_first_line(&path),
+            Some("wrong answer: expected 5, found 4".to_string())
+        );
+
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(read_first_line(&path), None);
+
+        assert_eq!(read_first_line(&dir.path().join("missing.chk")), None);
     }
 }
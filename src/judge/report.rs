@@ -0,0 +1,403 @@
+use super::{CaseResult, JudgeArgs, Verdict};
+use anyhow::Result;
+use console::strip_ansi_codes;
+use std::env;
+use std::fs::write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// XML の特殊文字をエスケープする
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn is_failure(verdict: &Verdict) -> bool {
+    !matches!(verdict, Verdict::Accepted | Verdict::Skipped)
+}
+
+/// (solver, case) ごとに 1 つの testcase を持つ JUnit 形式の XML を返す
+pub(super) fn junit_report(solvers: &[PathBuf], all_results: &[Vec<CaseResult>]) -> String {
+    let total: usize = all_results.iter().map(|results| results.len()).sum();
+    let failures = all_results
+        .iter()
+        .flatten()
+        .filter(|result| is_failure(&result.verdict))
+        .count();
+    let skipped = all_results
+        .iter()
+        .flatten()
+        .filter(|result| result.verdict == Verdict::Skipped)
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"kuroe\" tests=\"{total}\" failures=\"{failures}\" skipped=\"{skipped}\">\n"
+    ));
+    for (solver, results) in solvers.iter().zip(all_results) {
+        let solver_name = solver.file_stem().unwrap().to_string_lossy().to_string();
+        let time: Duration = results
+            .iter()
+            .filter_map(|result| result.info.elapsed)
+            .sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.6}\">\n",
+            escape_xml(&solver.to_string_lossy()),
+            results.len(),
+            results.iter().filter(|r| is_failure(&r.verdict)).count(),
+            results
+                .iter()
+                .filter(|r| r.verdict == Verdict::Skipped)
+                .count(),
+            time.as_secs_f64()
+        ));
+
+        for result in results {
+            let name = result.info.get_input_path().unwrap().to_string_lossy();
+            let time = result.info.elapsed.unwrap_or_default().as_secs_f64();
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{time:.6}\"",
+                escape_xml(&name),
+                escape_xml(&solver_name)
+            ));
+
+            match result.verdict {
+                Verdict::Accepted => xml.push_str("/>\n"),
+                Verdict::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                _ => {
+                    let verdict = escape_xml(&result.verdict.to_string());
+                    let description = escape_xml(&strip_ansi_codes(&result.describe()));
+                    xml.push_str(&format!(
+                        ">\n      <failure message=\"{verdict}\" type=\"{verdict}\">{description}</failure>\n    </testcase>\n"
+                    ));
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+
+    xml
+}
+
+pub(super) fn write_junit(
+    path: &Path,
+    solvers: &[PathBuf],
+    all_results: &[Vec<CaseResult>],
+) -> Result<()> {
+    write(path, junit_report(solvers, all_results))?;
+    Ok(())
+}
+
+/// report_dir から path へのリンク先を返す
+/// report_dir 以下にある場合は相対パス，それ以外は絶対パス
+fn link_path(report_dir: &Path, path: &Path) -> String {
+    let Ok(path) = path.canonicalize() else {
+        return path.to_string_lossy().to_string();
+    };
+    match report_dir
+        .canonicalize()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+    {
+        Some(relative) => relative.to_string_lossy().to_string(),
+        None => path.to_string_lossy().to_string(),
+    }
+}
+
+fn verdict_class(verdict: &Verdict) -> &'static str {
+    match verdict {
+        Verdict::Accepted => "ac",
+        Verdict::Skipped => "skip",
+        Verdict::TimeLimitExceed => "tle",
+        _ => "fail",
+    }
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+td.info { white-space: pre-wrap; font-family: monospace; }
+.ac { color: #1a7f37; font-weight: bold; }
+.fail { color: #cf222e; font-weight: bold; }
+.tle { color: #9a6700; font-weight: bold; }
+.skip { color: #888; }
+.bar { background: #54aeff; height: 1em; }
+.chart { width: 300px; }";
+
+/// solver ごとの表と実行時間の棒グラフを持つ単体の HTML を返す
+pub(super) fn html_report(
+    report_dir: &Path,
+    solvers: &[PathBuf],
+    all_results: &[Vec<CaseResult>],
+) -> String {
+    let max_elapsed = all_results
+        .iter()
+        .flatten()
+        .filter_map(|result| result.info.elapsed)
+        .max()
+        .unwrap_or_default();
+
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>kuroe judge report</title>\n");
+    html.push_str(&format!(
+        "<style>\n{HTML_STYLE}\n</style>\n</head>\n<body>\n"
+    ));
+    html.push_str("<h1>kuroe judge report</h1>\n");
+
+    for (solver, results) in solvers.iter().zip(all_results) {
+        let accepted = results
+            .iter()
+            .filter(|result| result.verdict == Verdict::Accepted)
+            .count();
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<p>{accepted} / {} AC</p>\n",
+            escape_xml(&solver.to_string_lossy()),
+            results.len()
+        ));
+        html.push_str("<table>\n<tr><th>status</th><th>input</th><th>output</th><th>time</th><th></th><th>info</th></tr>\n");
+
+        for result in results {
+            let input = result.info.get_input_path().unwrap();
+            let output = match result.info.get_output_path() {
+                Some(path) => format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_xml(&link_path(report_dir, path)),
+                    escape_xml(&path.file_name().unwrap().to_string_lossy())
+                ),
+                None => "".to_string(),
+            };
+            let (time, width) = match result.info.elapsed {
+                Some(elapsed) if !max_elapsed.is_zero() => (
+                    format!("{elapsed:.2?}"),
+                    elapsed.as_secs_f64() / max_elapsed.as_secs_f64() * 100.0,
+                ),
+                Some(elapsed) => (format!("{elapsed:.2?}"), 0.0),
+                None => ("".to_string(), 0.0),
+            };
+
+            html.push_str(&format!(
+                "<tr><td class=\"{}\">{}</td><td>{}</td><td>{output}</td><td>{time}</td><td class=\"chart\"><div class=\"bar\" style=\"width: {width:.1}%\"></div></td><td class=\"info\">{}</td></tr>\n",
+                verdict_class(&result.verdict),
+                escape_xml(&result.verdict.to_string()),
+                escape_xml(&input.to_string_lossy()),
+                escape_xml(&strip_ansi_codes(&result.describe()))
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+pub(super) fn write_html(
+    path: &Path,
+    solvers: &[PathBuf],
+    all_results: &[Vec<CaseResult>],
+) -> Result<()> {
+    let report_dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    write(path, html_report(report_dir, solvers, all_results))?;
+    Ok(())
+}
+
+/// 実行環境，solver ごとのコマンド，ケースごとの結果を記録したセッションログを返す
+/// commands は solver ごとのコンパイル・実行コマンド
+pub(super) fn session_log(
+    header: &[String],
+    checker: &[String],
+    solvers: &[PathBuf],
+    commands: &[Vec<String>],
+    all_results: &[Vec<CaseResult>],
+    timelimit: f64,
+) -> String {
+    let mut log = String::new();
+    for line in header {
+        log.push_str(&format!("{line}\n"));
+    }
+
+    log.push_str("\n[checker]\n");
+    for command in checker {
+        log.push_str(&format!("{command}\n"));
+    }
+
+    for ((solver, commands), results) in solvers.iter().zip(commands).zip(all_results) {
+        log.push_str(&format!("\n[solver] {solver:?}\n"));
+        for command in commands {
+            log.push_str(&format!("{command}\n"));
+        }
+        for result in results {
+            let info = &result.info;
+            let mut fields = vec![
+                result.verdict.to_string(),
+                format!("{:?}", info.get_input_path().unwrap()),
+                format!("tl = {}", info.timelimit.unwrap_or(timelimit)),
+            ];
+            fields.extend(info.elapsed.map(|elapsed| format!("time = {elapsed:?}")));
+            fields.extend(info.cpu_time.map(|cpu_time| format!("cpu = {cpu_time:?}")));
+            fields.extend(info.memory.map(|memory| format!("memory = {memory} KiB")));
+            log.push_str(&format!("{}\n", fields.join(" ")));
+        }
+    }
+
+    log
+}
+
+/// uname(2) による OS 名，カーネルのリリース，ホスト名，マシン名
+fn uname() -> Option<String> {
+    let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut name) } != 0 {
+        return None;
+    }
+    let field = |field: &[libc::c_char]| {
+        let bytes: Vec<u8> = field
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).to_string()
+    };
+    Some(format!(
+        "{} {} {} {}",
+        field(&name.sysname),
+        field(&name.release),
+        field(&name.nodename),
+        field(&name.machine)
+    ))
+}
+
+pub(super) fn write_session_log(
+    path: &Path,
+    args: &JudgeArgs,
+    checker: &[String],
+    solvers: &[PathBuf],
+    commands: &[Vec<String>],
+    all_results: &[Vec<CaseResult>],
+) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let mut header = vec![
+        format!("kuroe {}", env!("CARGO_PKG_VERSION")),
+        format!("time = {timestamp} (unix)"),
+        format!("command = {}", env::args().collect::<Vec<_>>().join(" ")),
+        format!("cwd = {:?}", env::current_dir()?),
+        format!("os = {} {}", env::consts::OS, env::consts::ARCH),
+    ];
+    header.extend(uname().map(|uname| format!("uname = {uname}")));
+    header.extend(std::thread::available_parallelism().map(|cpus| format!("cpus = {cpus}")));
+    header.extend(env::var_os("PATH").map(|path| format!("PATH = {path:?}")));
+    header.push(format!("{args:?}"));
+    write(
+        path,
+        session_log(
+            &header,
+            checker,
+            solvers,
+            commands,
+            all_results,
+            args.timelimit,
+        ),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judge::JudgeInfo;
+
+    #[test]
+    fn test_session_log() {
+        let info = JudgeInfo::new()
+            .input(Path::new("a.in"))
+            .elapsed(Duration::from_millis(5))
+            .memory(1024);
+        let log = session_log(
+            &["kuroe".to_string()],
+            &["compare: built-in diff".to_string()],
+            &[PathBuf::from("sol.cpp")],
+            &[vec![
+                "cwd = \"/tmp/run\"".to_string(),
+                "compile: g++ sol.cpp".to_string(),
+            ]],
+            &[vec![CaseResult::new(
+                Verdict::Accepted,
+                info.timelimit(3.0),
+            )]],
+            2.0,
+        );
+        assert!(log.starts_with("kuroe\n"));
+        assert!(log.contains("[checker]\ncompare: built-in diff\n"));
+        assert!(log.contains("[solver] \"sol.cpp\"\ncwd = \"/tmp/run\"\ncompile: g++ sol.cpp\n"));
+        assert!(log.contains("AC \"a.in\" tl = 3 time = 5ms memory = 1024 KiB\n"));
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(escape_xml("a<b>&\"c'"), "a&lt;b&gt;&amp;&quot;c&apos;");
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let info = |name: &str| {
+            JudgeInfo::new()
+                .input(Path::new(name))
+                .output(Path::new("out"))
+                .elapsed(Duration::from_millis(10))
+        };
+        let xml = junit_report(
+            &[PathBuf::from("sol.cpp")],
+            &[vec![
+                CaseResult::new(Verdict::Accepted, info("a.in")),
+                CaseResult::new(Verdict::WrongAnswer, info("b.in")),
+                CaseResult::new(Verdict::Skipped, JudgeInfo::new().input(Path::new("c.in"))),
+            ]],
+        );
+
+        assert!(
+            xml.contains("<testsuites name=\"kuroe\" tests=\"3\" failures=\"1\" skipped=\"1\">")
+        );
+        assert!(xml.contains("<testcase name=\"a.in\" classname=\"sol\" time=\"0.010000\"/>"));
+        assert!(xml.contains("<failure message=\"WA\" type=\"WA\">"));
+        assert!(xml.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn test_html_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("a.out");
+        std::fs::write(&output, "").unwrap();
+
+        let html = html_report(
+            dir.path(),
+            &[PathBuf::from("sol.cpp")],
+            &[vec![CaseResult::new(
+                Verdict::WrongAnswer,
+                JudgeInfo::new()
+                    .input(Path::new("<a>.in"))
+                    .output(&output)
+                    .elapsed(Duration::from_millis(10)),
+            )]],
+        );
+
+        assert!(html.contains("<h2>sol.cpp</h2>"));
+        assert!(html.contains("<td class=\"fail\">WA</td>"));
+        assert!(html.contains("&lt;a&gt;.in"));
+        assert!(html.contains("<a href=\"a.out\">a.out</a>"));
+        assert!(html.contains("width: 100.0%"));
+    }
+}
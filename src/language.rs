@@ -1,15 +1,21 @@
 use anyhow::{bail, ensure, Context, Result};
 use log::debug;
 use regex::Regex;
-use std::path::Path;
+use std::fs::File;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitStatus, Stdio};
-use std::time::Duration;
-use wait_timeout::ChildExt;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{mpsc, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) enum ExecuteStatus {
     Success,
     TimeLimitExceed,
+    /// 出力サイズの上限を超えた
+    OutputLimitExceed,
     Fail,
 }
 
@@ -24,6 +30,7 @@ impl std::fmt::Display for ExecuteStatus {
         match self {
             ExecuteStatus::Success => write!(f, "OK")?,
             ExecuteStatus::TimeLimitExceed => write!(f, "TLE")?,
+            ExecuteStatus::OutputLimitExceed => write!(f, "OLE")?,
             ExecuteStatus::Fail => write!(f, "FAIL")?,
         };
 
@@ -35,17 +42,114 @@ impl From<ExitStatus> for ExecuteStatus {
     fn from(status: ExitStatus) -> ExecuteStatus {
         if status.success() {
             ExecuteStatus::Success
+        } else if status.signal() == Some(libc::SIGXFSZ) {
+            // RLIMIT_FSIZE を超えて書き込もうとした
+            ExecuteStatus::OutputLimitExceed
         } else {
             ExecuteStatus::Fail
         }
     }
 }
 
-#[derive(Debug)]
+/// 1 回の実行の詳細（使用した資源と終了コード）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ExecuteDetail {
+    /// 実行時間（wall-clock）
+    pub(crate) elapsed: Duration,
+    /// CPU 時間（user + sys）
+    pub(crate) cpu_time: Duration,
+    /// 最大常駐セットサイズ（KiB）
+    pub(crate) max_rss: u64,
+    /// 終了コード（TLE やシグナルで終了した場合は None）
+    pub(crate) exit_code: Option<i32>,
+    /// 終了させたシグナル（TLE で kill した場合は None）
+    pub(crate) signal: Option<i32>,
+}
+
+/// 同時に実行中として登録できるプロセスグループの数
+const MAX_PROCESS_GROUPS: usize = 1024;
+
+/// 実行中の子プロセスのプロセスグループ（空きは 0）
+/// シグナルハンドラから参照するため，ロックを使わない固定長の配列とする
+static PROCESS_GROUPS: [AtomicI32; MAX_PROCESS_GROUPS] =
+    [const { AtomicI32::new(0) }; MAX_PROCESS_GROUPS];
+
+/// SIGINT, SIGTERM を受けたら実行中のプロセスグループを全て kill してから，既定の動作で終了する
+/// 子プロセスは端末のフォアグラウンドのプロセスグループの外で実行されるため，Ctrl-C が届かない
+extern "C" fn kill_process_groups(signal: libc::c_int) {
+    for group in &PROCESS_GROUPS {
+        let pgid = group.load(Ordering::SeqCst);
+        if pgid > 0 {
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+        }
+    }
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+fn install_signal_handlers() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        let handler = kill_process_groups as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    });
+}
+
+/// 実行中のプロセスグループの登録
+/// drop 時に（子プロセスが正常終了していても残っている孫プロセスごと）グループ全体を kill し，登録を解除する
+struct ProcessGroupGuard {
+    pgid: libc::pid_t,
+    slot: Option<usize>,
+}
+
+impl ProcessGroupGuard {
+    fn new(pgid: libc::pid_t) -> Self {
+        install_signal_handlers();
+        let slot = PROCESS_GROUPS.iter().position(|group| {
+            group
+                .compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        ProcessGroupGuard { pgid, slot }
+    }
+
+    /// グループ全体を kill する．既に全て終了していれば kill は失敗するが問題ない
+    fn kill(&self) {
+        unsafe {
+            libc::killpg(self.pgid, libc::SIGKILL);
+        }
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        self.kill();
+        if let Some(slot) = self.slot {
+            PROCESS_GROUPS[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+fn timeval_to_duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+#[derive(Debug, Clone)]
 pub(crate) struct CommandStep {
     program: String,
     args: Vec<String>,
     ignore_additional_args: bool,
+    /// 書き込めるファイルサイズの上限（バイト）
+    output_limit: Option<u64>,
+    /// 仮想メモリの上限（バイト）
+    memory_limit: Option<u64>,
+    /// 追加の環境変数
+    envs: Vec<(String, String)>,
 }
 
 impl CommandStep {
@@ -54,6 +158,9 @@ impl CommandStep {
             program,
             args,
             ignore_additional_args: false,
+            output_limit: None,
+            memory_limit: None,
+            envs: Vec::new(),
         }
     }
 
@@ -62,9 +169,32 @@ impl CommandStep {
             program,
             args,
             ignore_additional_args: true,
+            output_limit: None,
+            memory_limit: None,
+            envs: Vec::new(),
         }
     }
 
+    /// 実行時に書き込めるファイルサイズの上限を設定する
+    /// 上限を超えると ExecuteStatus::OutputLimitExceed となる
+    pub(crate) fn output_limit(mut self, limit: u64) -> Self {
+        self.output_limit = Some(limit);
+        self
+    }
+
+    /// 実行時に確保できる仮想メモリの上限を設定する
+    /// 上限を超えた確保は失敗する（多くの場合，異常終了する）
+    pub(crate) fn memory_limit(mut self, limit: u64) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// 実行時に環境変数 key=value を設定する
+    pub(crate) fn env(mut self, key: &str, value: &str) -> Self {
+        self.envs.push((key.to_string(), value.to_string()));
+        self
+    }
+
     pub(crate) fn execute<P: AsRef<Path>, T: Into<Stdio>, U: Into<Stdio>, V: Into<Stdio>>(
         &self,
         current_dir: P,
@@ -74,6 +204,32 @@ impl CommandStep {
         stderr: V,
         time_limit: Duration,
     ) -> Result<ExecuteStatus> {
+        let (status, _) = self.execute_with_detail(
+            current_dir,
+            additional_args,
+            stdin,
+            stdout,
+            stderr,
+            time_limit,
+        )?;
+        Ok(status)
+    }
+
+    /// execute に加えて実行時間，資源使用量と終了コードを返す
+    pub(crate) fn execute_with_detail<
+        P: AsRef<Path>,
+        T: Into<Stdio>,
+        U: Into<Stdio>,
+        V: Into<Stdio>,
+    >(
+        &self,
+        current_dir: P,
+        additional_args: Vec<String>,
+        stdin: T,
+        stdout: U,
+        stderr: V,
+        time_limit: Duration,
+    ) -> Result<(ExecuteStatus, ExecuteDetail)> {
         let args = if !self.ignore_additional_args {
             [&self.args[..], &additional_args[..]].concat()
         } else {
@@ -81,49 +237,179 @@ impl CommandStep {
         };
         debug!("$ {:} {:}", self.program, args.join(" "));
 
-        let mut child = Command::new(&self.program)
+        let mut command = Command::new(&self.program);
+        command
             .args(args)
             .current_dir(current_dir)
             .stdin(stdin)
             .stdout(stdout)
             .stderr(stderr)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
+            // 子プロセスが生成したプロセスもまとめて kill できるよう，新しいプロセスグループで実行する
+            .process_group(0);
+        if let Some(limit) = self.output_limit {
+            let rlimit = libc::rlimit {
+                rlim_cur: limit as libc::rlim_t,
+                rlim_max: limit as libc::rlim_t,
+            };
+            // fork 後 exec 前に子プロセス側で上限を設定する
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setrlimit(libc::RLIMIT_FSIZE, &rlimit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        if let Some(limit) = self.memory_limit {
+            let rlimit = libc::rlimit {
+                rlim_cur: limit as libc::rlim_t,
+                rlim_max: limit as libc::rlim_t,
+            };
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setrlimit(libc::RLIMIT_AS, &rlimit) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        let timer = Instant::now();
+        let child = command
             .spawn()
             .with_context(|| format!("Failed to execute {:?}", self))?;
         debug!("{:#?}", child);
 
-        let status = match child.wait_timeout(time_limit)? {
-            Some(status) => ExecuteStatus::from(status),
-            None => {
+        // wait4 で終了を待ち，資源使用量を取得する
+        let pid = child.id() as libc::pid_t;
+        let group = ProcessGroupGuard::new(pid);
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut status = 0;
+            let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+            let ret = unsafe { libc::wait4(pid, &mut status, 0, &mut usage) };
+            let _ = sender.send((ret, status, usage, timer.elapsed()));
+        });
+
+        let (status, (ret, raw_status, usage, elapsed)) = match receiver.recv_timeout(time_limit) {
+            Ok(waited) => (None, waited),
+            Err(_) => {
                 // child hasn't exited yet
-                child.kill().unwrap();
-                child.wait().unwrap();
-                ExecuteStatus::TimeLimitExceed
+                // プロセスグループ全体を kill する（child は wait4 のスレッドが回収する）
+                group.kill();
+                (Some(ExecuteStatus::TimeLimitExceed), receiver.recv()?)
             }
         };
-        Ok(status)
+        // 正常終了した場合も，残っている孫プロセスをグループごと kill する
+        drop(group);
+        ensure!(ret == pid, "failed to wait {:?}", self);
+
+        let exit_status = ExitStatus::from_raw(raw_status);
+        let (exit_code, signal) = if status.is_none() {
+            (exit_status.code(), exit_status.signal())
+        } else {
+            (None, None)
+        };
+        let status = status.unwrap_or_else(|| ExecuteStatus::from(exit_status));
+        let detail = ExecuteDetail {
+            elapsed,
+            cpu_time: timeval_to_duration(usage.ru_utime) + timeval_to_duration(usage.ru_stime),
+            max_rss: usage.ru_maxrss as u64,
+            exit_code,
+            signal,
+        };
+        debug!("status = {status:?}, detail = {detail:?}");
+
+        Ok((status, detail))
+    }
+}
+
+/// シェルにそのまま貼り付けられる形式で表示する
+impl std::fmt::Display for CommandStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let quote = |arg: &str| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+%@".contains(c))
+            {
+                arg.to_string()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        };
+
+        for (key, value) in &self.envs {
+            write!(f, "{}={} ", key, quote(value))?;
+        }
+        write!(f, "{}", quote(&self.program))?;
+        for arg in &self.args {
+            write!(f, " {}", quote(arg))?;
+        }
+        Ok(())
     }
 }
 
-pub(crate) trait Language {
+pub(crate) trait Language: Send + Sync {
     fn is_valid_ext(&self, ext: &str) -> bool;
     fn compile(&self, target: &Path) -> Result<Vec<CommandStep>>;
     fn run(&self, target: &Path) -> Result<CommandStep>;
+
+    /// target を sources（grader 等）と一緒にコンパイルする
+    /// 対応していない言語では sources が空の場合のみ成功する
+    fn compile_with_sources(&self, target: &Path, sources: &[PathBuf]) -> Result<Vec<CommandStep>> {
+        ensure!(
+            sources.is_empty(),
+            "compiling {target:?} with additional sources is not supported"
+        );
+        self.compile(target)
+    }
+}
+
+/// gcc/g++ に渡すソースファイルとインクルードパスを返す
+/// ヘッダ（.h, .hpp）はコンパイル対象とせず，そのディレクトリをインクルードパスに加える
+fn gcc_source_args(target: &Path, sources: &[PathBuf]) -> Result<Vec<String>> {
+    let mut files = vec![target.canonicalize()?.to_string_lossy().to_string()];
+    let mut includes = Vec::new();
+    for source in sources {
+        let source = source.canonicalize()?;
+        let include = format!("-I{}", source.parent().unwrap().to_string_lossy());
+        if !includes.contains(&include) {
+            includes.push(include);
+        }
+
+        if !source
+            .extension()
+            .is_some_and(|ext| ext == "h" || ext == "hpp")
+        {
+            files.push(source.to_string_lossy().to_string());
+        }
+    }
+
+    Ok([includes, files].concat())
 }
 
 pub(crate) struct Clang;
 impl Language for Clang {
     fn is_valid_ext(&self, ext: &str) -> bool {
-        return ext == "c";
+        ext == "c"
     }
 
     fn compile(&self, target: &Path) -> Result<Vec<CommandStep>> {
+        self.compile_with_sources(target, &[])
+    }
+
+    fn compile_with_sources(&self, target: &Path, sources: &[PathBuf]) -> Result<Vec<CommandStep>> {
         Ok(vec![CommandStep::new(
             "gcc".to_string(),
-            vec![
-                "-std=c11".to_string(),
-                "-O2".to_string(),
-                target.canonicalize()?.to_string_lossy().to_string(),
-            ],
+            [
+                vec!["-std=c11".to_string(), "-O2".to_string()],
+                gcc_source_args(target, sources)?,
+            ]
+            .concat(),
         )])
     }
 
@@ -135,17 +421,21 @@ impl Language for Clang {
 pub(crate) struct Cpp;
 impl Language for Cpp {
     fn is_valid_ext(&self, ext: &str) -> bool {
-        return ext == "cpp" || ext == "cc";
+        ext == "cpp" || ext == "cc"
     }
 
     fn compile(&self, target: &Path) -> Result<Vec<CommandStep>> {
+        self.compile_with_sources(target, &[])
+    }
+
+    fn compile_with_sources(&self, target: &Path, sources: &[PathBuf]) -> Result<Vec<CommandStep>> {
         Ok(vec![CommandStep::new(
             "g++".to_string(),
-            vec![
-                "-std=c++20".to_string(),
-                "-O2".to_string(),
-                target.canonicalize()?.to_string_lossy().to_string(),
-            ],
+            [
+                vec!["-std=c++20".to_string(), "-O2".to_string()],
+                gcc_source_args(target, sources)?,
+            ]
+            .concat(),
         )])
     }
 
@@ -157,7 +447,7 @@ impl Language for Cpp {
 pub(crate) struct Python;
 impl Language for Python {
     fn is_valid_ext(&self, ext: &str) -> bool {
-        return ext == "py";
+        ext == "py"
     }
 
     fn compile(&self, _target: &Path) -> Result<Vec<CommandStep>> {
@@ -175,7 +465,7 @@ impl Language for Python {
 pub(crate) struct Txt;
 impl Language for Txt {
     fn is_valid_ext(&self, ext: &str) -> bool {
-        return ext == "txt" || ext == "in";
+        ext == "txt" || ext == "in"
     }
 
     fn compile(&self, _target: &Path) -> Result<Vec<CommandStep>> {
@@ -190,6 +480,28 @@ impl Language for Txt {
     }
 }
 
+/// スキーマファイルを kuroe 自身（kuroe schema）で実行する
+pub(crate) struct Schema;
+impl Language for Schema {
+    fn is_valid_ext(&self, ext: &str) -> bool {
+        ext == "schema"
+    }
+
+    fn compile(&self, _target: &Path) -> Result<Vec<CommandStep>> {
+        Ok(Vec::new())
+    }
+
+    fn run(&self, target: &Path) -> Result<CommandStep> {
+        Ok(CommandStep::new(
+            std::env::current_exe()?.to_string_lossy().to_string(),
+            vec![
+                "schema".to_string(),
+                target.canonicalize()?.to_string_lossy().to_string(),
+            ],
+        ))
+    }
+}
+
 pub(crate) struct CustomLang {
     ext: Regex,
     compile: Vec<String>,
@@ -211,11 +523,20 @@ impl CustomLang {
 }
 impl Language for CustomLang {
     fn is_valid_ext(&self, ext: &str) -> bool {
-        return self.ext.is_match(ext);
+        self.ext.is_match(ext)
     }
 
     fn compile(&self, target: &Path) -> Result<Vec<CommandStep>> {
-        let target = target.canonicalize()?.to_string_lossy().to_string();
+        self.compile_with_sources(target, &[])
+    }
+
+    /// %(target) は target と sources を空白区切りで並べたものに置換される
+    fn compile_with_sources(&self, target: &Path, sources: &[PathBuf]) -> Result<Vec<CommandStep>> {
+        let mut targets = vec![target.canonicalize()?.to_string_lossy().to_string()];
+        for source in sources {
+            targets.push(source.canonicalize()?.to_string_lossy().to_string());
+        }
+        let target = targets.join(" ");
 
         let mut cmds = Vec::new();
         for command in &self.compile {
@@ -243,6 +564,7 @@ pub(crate) fn default_languages() -> Vec<Box<dyn Language + 'static>> {
         Box::new(Cpp),
         Box::new(Python),
         Box::new(Txt),
+        Box::new(Schema),
     ]
 }
 
@@ -252,10 +574,10 @@ pub(crate) fn default_languages() -> Vec<Box<dyn Language + 'static>> {
 pub(crate) fn detect_language<'a>(
     ext: &str,
     langs: &'a Vec<Box<dyn Language>>,
-) -> Result<&'a Box<dyn Language>> {
+) -> Result<&'a dyn Language> {
     for lang in langs {
         if lang.is_valid_ext(ext) {
-            return Ok(lang);
+            return Ok(lang.as_ref());
         }
     }
     bail!("no language detected");
@@ -267,35 +589,94 @@ pub(crate) fn compile_and_get_runstep<P: AsRef<Path>>(
     target: &Path,
     langs: &Vec<Box<dyn Language>>,
 ) -> Result<CommandStep> {
-    let lang = {
-        let ext = target
-            .extension()
-            .with_context(|| format!("{:?} not found", target))?
-            .to_string_lossy()
-            .to_string();
-        detect_language(&ext, &langs)?
-    };
+    compile_and_get_runstep_with_sources(current_dir, target, &[], langs)
+}
+
+/// target を sources と一緒に compile して runstep を返す
+pub(crate) fn compile_and_get_runstep_with_sources<P: AsRef<Path>>(
+    current_dir: P,
+    target: &Path,
+    sources: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+) -> Result<CommandStep> {
+    compile_and_get_steps(current_dir, target, sources, langs).map(|(_, runstep)| runstep)
+}
+
+/// target を sources と一緒に compile して，実行した compile のコマンド列と runstep を返す
+pub(crate) fn compile_and_get_steps<P: AsRef<Path>>(
+    current_dir: P,
+    target: &Path,
+    sources: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+) -> Result<(Vec<CommandStep>, CommandStep)> {
+    compile_with_stderr(current_dir, target, sources, langs, || Ok(Stdio::inherit()))
+}
+
+/// target を sources と一緒に compile して，実行した compile のコマンド列と runstep を返す
+/// コンパイラの標準エラー出力は端末ではなく log に保存される
+pub(crate) fn compile_and_get_steps_with_log<P: AsRef<Path>>(
+    current_dir: P,
+    target: &Path,
+    sources: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+    log: &Path,
+) -> Result<(Vec<CommandStep>, CommandStep)> {
+    let log = File::create(log)?;
+    compile_with_stderr(current_dir, target, sources, langs, || {
+        Ok(Stdio::from(log.try_clone()?))
+    })
+}
 
-    for step in lang.compile(&target)? {
+fn compile_with_stderr<P: AsRef<Path>, F: Fn() -> Result<Stdio>>(
+    current_dir: P,
+    target: &Path,
+    sources: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+    stderr: F,
+) -> Result<(Vec<CommandStep>, CommandStep)> {
+    let (compile_steps, runstep) = get_steps(target, sources, langs)?;
+    for step in &compile_steps {
         let status = step.execute(
             &current_dir,
             Vec::new(),
             Stdio::null(),
             Stdio::null(),
-            Stdio::inherit(),
+            stderr()?,
             Duration::from_secs(10),
         )?;
 
         ensure!(status.success(), "failed to compile");
     }
 
-    lang.run(&target)
+    Ok((compile_steps, runstep))
+}
+
+/// target を sources と一緒に compile するコマンド列と runstep を返す（実行はしない）
+pub(crate) fn get_steps(
+    target: &Path,
+    sources: &[PathBuf],
+    langs: &Vec<Box<dyn Language>>,
+) -> Result<(Vec<CommandStep>, CommandStep)> {
+    let lang = {
+        let ext = target
+            .extension()
+            .with_context(|| format!("{:?} not found", target))?
+            .to_string_lossy()
+            .to_string();
+        detect_language(&ext, langs)?
+    };
+
+    Ok((
+        lang.compile_with_sources(target, sources)?,
+        lang.run(target)?,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::{read_to_string, File};
+    use std::io::{Read, Seek, SeekFrom};
     use tempfile::tempdir;
 
     #[test]
@@ -334,6 +715,199 @@ mod tests {
             .success());
     }
 
+    #[test]
+    fn test_execute_with_detail() {
+        let step = CommandStep::new("sleep".to_string(), vec!["0.1".to_string()]);
+        let (status, detail) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert!(status.success());
+        assert!(detail.elapsed >= Duration::from_millis(100));
+        assert!(detail.cpu_time < detail.elapsed);
+        assert!(detail.max_rss > 0);
+        assert_eq!(detail.exit_code, Some(0));
+
+        let step = CommandStep::new("sleep".to_string(), vec!["10".to_string()]);
+        let (status, detail) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_millis(100),
+            )
+            .unwrap();
+        assert_eq!(status, ExecuteStatus::TimeLimitExceed);
+        assert!(detail.elapsed < Duration::from_secs(10));
+        assert_eq!(detail.exit_code, None);
+    }
+
+    #[test]
+    fn test_kill_process_group() {
+        let dir = tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        // 孫プロセスが TLE 後に生き残っていれば marker が作られる
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                format!("(sleep 1; touch {}) & wait", marker.to_string_lossy()),
+            ],
+        );
+        let (status, _) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_millis(200),
+            )
+            .unwrap();
+        assert_eq!(status, ExecuteStatus::TimeLimitExceed);
+
+        // 子プロセスが正常終了した後に残った孫プロセスも kill する
+        let orphan_marker = dir.path().join("orphan_marker");
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                format!(
+                    "(sleep 1; touch {}) & exit 0",
+                    orphan_marker.to_string_lossy()
+                ),
+            ],
+        );
+        let (status, _) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_secs(5),
+            )
+            .unwrap();
+        assert!(status.success());
+
+        thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+        assert!(!orphan_marker.exists());
+    }
+
+    #[test]
+    fn test_display_command_step() {
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "echo a b".to_string()],
+        );
+        assert_eq!(step.to_string(), "sh -c 'echo a b'");
+
+        let step = CommandStep::new("echo".to_string(), vec!["it's".to_string()]);
+        assert_eq!(step.to_string(), r"echo 'it'\''s'");
+
+        let step = CommandStep::new("g++".to_string(), vec!["-O2".to_string()]);
+        assert_eq!(step.to_string(), "g++ -O2");
+
+        let step = CommandStep::new("./a.out".to_string(), Vec::new()).env("SEED", "4 2");
+        assert_eq!(step.to_string(), "SEED='4 2' ./a.out");
+    }
+
+    #[test]
+    fn test_env() {
+        let dir = tempdir().unwrap();
+        let output_path = dir.path().join("output.txt");
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "printf %s \"$SEED\"".to_string()],
+        )
+        .env("SEED", "42");
+        step.execute(
+            "./",
+            Vec::new(),
+            Stdio::null(),
+            File::create(&output_path).unwrap(),
+            Stdio::null(),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        assert_eq!(read_to_string(&output_path).unwrap(), "42");
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "exit 7".to_string()],
+        );
+        let (status, detail) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                Stdio::null(),
+                Stdio::null(),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(status, ExecuteStatus::Fail);
+        assert_eq!(detail.exit_code, Some(7));
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let output = tempfile::tempfile().unwrap();
+        let step = CommandStep::new("yes".to_string(), Vec::new()).output_limit(1024);
+        let (status, _) = step
+            .execute_with_detail(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                output.try_clone().unwrap(),
+                Stdio::null(),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(status, ExecuteStatus::OutputLimitExceed);
+        assert!(output.metadata().unwrap().len() <= 1024);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let output = tempfile::tempfile().unwrap();
+        let step = CommandStep::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "ulimit -v".to_string()],
+        )
+        .memory_limit(64 * 1024 * 1024);
+        let status = step
+            .execute(
+                "./",
+                Vec::new(),
+                Stdio::null(),
+                output.try_clone().unwrap(),
+                Stdio::null(),
+                Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(status, ExecuteStatus::Success);
+
+        let mut limit = String::new();
+        let mut output = output;
+        output.seek(SeekFrom::Start(0)).unwrap();
+        output.read_to_string(&mut limit).unwrap();
+        // ulimit -v は KiB 単位
+        assert_eq!(limit.trim(), "65536");
+    }
+
     #[test]
     fn test_language() {
         assert!(Clang.is_valid_ext("c"));
@@ -503,6 +1077,71 @@ mod tests {
         assert_eq!(read_to_string(&output_path).unwrap(), "hello\n");
     }
 
+    #[test]
+    fn test_compile_with_sources() {
+        let dir = tempdir().unwrap();
+        let solver = dir.path().join("solver.cpp");
+        let grader_dir = dir.path().join("grader");
+        std::fs::create_dir(&grader_dir).unwrap();
+        let grader = grader_dir.join("grader.cpp");
+        let header = grader_dir.join("grader.h");
+        std::fs::write(
+            &solver,
+            "#include \"grader.h\"\nint solve(int x) { return x + 1; }\n",
+        )
+        .unwrap();
+        std::fs::write(&header, "int solve(int x);\n").unwrap();
+        std::fs::write(
+            &grader,
+            "#include <cstdio>\n#include \"grader.h\"\nint main() { printf(\"%d\", solve(1)); }\n",
+        )
+        .unwrap();
+
+        let steps = Cpp
+            .compile_with_sources(&solver, &[grader.clone(), header.clone()])
+            .unwrap();
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].args.contains(&format!(
+            "-I{}",
+            grader_dir.canonicalize().unwrap().to_string_lossy()
+        )));
+        assert!(!steps[0]
+            .args
+            .contains(&header.canonicalize().unwrap().to_string_lossy().to_string()));
+
+        let langs: Vec<Box<dyn Language>> = vec![Box::new(Cpp)];
+        let runstep =
+            compile_and_get_runstep_with_sources(&dir, &solver, &[grader, header], &langs).unwrap();
+        let output_path = dir.path().join("output.txt");
+        runstep
+            .execute(
+                &dir,
+                Vec::new(),
+                Stdio::null(),
+                File::create(&output_path).unwrap(),
+                Stdio::null(),
+                Duration::from_secs(2),
+            )
+            .unwrap();
+        assert_eq!(read_to_string(&output_path).unwrap(), "2");
+
+        assert!(Python
+            .compile_with_sources(&solver, &[dir.path().join("grader.py")])
+            .is_err());
+    }
+
+    #[test]
+    fn test_compile_with_log() {
+        let dir = tempdir().unwrap();
+        let solver = dir.path().join("broken.cpp");
+        std::fs::write(&solver, "int main() { return undefined_variable; }\n").unwrap();
+        let log = dir.path().join("compile.log");
+
+        let langs: Vec<Box<dyn Language>> = vec![Box::new(Cpp)];
+        assert!(compile_and_get_steps_with_log(&dir, &solver, &[], &langs, &log).is_err());
+        assert!(read_to_string(&log).unwrap().contains("undefined_variable"));
+    }
+
     #[test]
     fn test_compile_and_run_custom_lang() {
         let lang = CustomLang::new(
@@ -1,3 +1,4 @@
+mod bundle;
 mod generate;
 mod judge;
 mod language;
@@ -6,7 +7,6 @@ mod utils;
 mod validate;
 
 use clap::{Parser, Subcommand};
-use env_logger;
 use log::error;
 use std::process::ExitCode;
 
@@ -18,6 +18,8 @@ struct Cli {
     command: Commands,
 }
 
+// 起動時に 1 度だけ生成されるため，バリアント間のサイズ差は問題にならない
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum Commands {
     #[command(arg_required_else_help = true)]
@@ -35,6 +37,13 @@ enum Commands {
     #[command(arg_required_else_help = true)]
     #[command(about = "judge a solver")]
     Judge(judge::JudgeArgs),
+
+    #[command(about = "bundle testcases into multitest inputs (or split them back)")]
+    Bundle(bundle::BundleArgs),
+
+    /// .schema ジェネレータの実行に用いる
+    #[command(hide = true)]
+    Schema(generate::schema::SchemaArgs),
 }
 
 fn main() -> ExitCode {
@@ -74,5 +83,21 @@ fn main() -> ExitCode {
                 ExitCode::SUCCESS
             }
         }
+        Commands::Bundle(args) => {
+            if let Err(err) = bundle::root(args) {
+                error!("{err:?}");
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Commands::Schema(args) => {
+            if let Err(err) = generate::schema::root(args) {
+                error!("{err:?}");
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
     }
 }
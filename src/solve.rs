@@ -1,21 +1,22 @@
 use crate::language::{compile_and_get_runstep, CommandStep, ExecuteStatus};
-use crate::utils::{find_files, make_languages};
+use crate::utils::{find_files, inflate_input, input_name, is_input, make_languages};
 use anyhow::{bail, ensure, Result};
 use clap::Args;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{info, warn};
-use std::fs::{create_dir_all, File};
+use std::fs::{copy, create_dir_all, read, remove_file, File};
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 use std::time::Duration;
 use tabled::{Table, Tabled};
 use tempfile::TempDir;
 
 #[derive(Debug, Args)]
 pub(super) struct SolveArgs {
-    /// path to the solver
-    #[arg(value_name = "SOLVER")]
-    solver: PathBuf,
+    /// path to the solver. With several solvers, the answers are written into OUTDIR/<solver name>
+    #[arg(value_name = "SOLVER", required = true)]
+    solvers: Vec<PathBuf>,
 
     /// directory containing the testcases or path to the testcase(*.in)
     #[arg(short, long, default_value = "./testcases/input")]
@@ -25,7 +26,7 @@ pub(super) struct SolveArgs {
     #[arg(short, long, default_value_t = false)]
     recursive: bool,
 
-    ///
+    /// output directory
     #[arg(short, long, default_value = "./testcases/answer")]
     outdir: PathBuf,
 
@@ -33,6 +34,18 @@ pub(super) struct SolveArgs {
     #[arg(visible_alias = "tl", long, default_value_t = 10.0)]
     timelimit: f64,
 
+    /// number of testcases solved concurrently
+    #[arg(short, long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: u32,
+
+    /// do not solve testcases whose answer already exists
+    #[arg(long, default_value_t = false, conflicts_with = "force")]
+    skip_existing: bool,
+
+    /// overwrite existing answers (without --skip-existing or --force, solve refuses to run if any answer exists)
+    #[arg(long, default_value_t = false)]
+    force: bool,
+
     /// COMMAND[0:-1] are the compile commands. COMMAND[-1] is execute command
     #[arg(
         short,
@@ -45,96 +58,374 @@ pub(super) struct SolveArgs {
 }
 
 /// answer 出力先を返す
-fn solve<P: AsRef<Path>>(
+/// 標準エラー出力は空でなければ stderr_log_path に保存する
+/// 解答は正常終了した場合のみ保存し，失敗した場合は以前の解答も古いので残さない
+pub(crate) fn solve<P: AsRef<Path>>(
     current_dir: P,
     target: &Path,
     outdir: &Path,
     run: &CommandStep,
     timelimit: f64,
 ) -> Result<(ExecuteStatus, PathBuf)> {
-    let input = File::open(&target)?;
+    // gzip 圧縮された入力は展開してから渡す
+    let inflate_dir = TempDir::new()?;
+    let input = File::open(inflate_input(target, inflate_dir.path())?)?;
 
-    let name = target.file_stem().unwrap().to_string_lossy().to_string();
-    let answer_path = outdir.join(format!("{name}.ans"));
-    let answer = File::create(&answer_path)?;
+    let answer_path = answer_path(outdir, target);
+    // 途中までの解答が残らないよう，一時ファイルに書き込んでから置き換える
+    let answer = tempfile::Builder::new()
+        .prefix(".")
+        .suffix(".tmp")
+        .tempfile_in(outdir)?;
 
-    if let Ok(status) = run.execute(
+    // 空のログを残さないよう，一時ファイルに書き込んでから保存する
+    let stderr_path = inflate_dir.path().join("stderr");
+    let result = run.execute(
         current_dir,
         Vec::new(),
         input,
-        answer,
-        Stdio::null(),
+        answer.reopen()?,
+        File::create(&stderr_path)?,
         Duration::from_secs_f64(timelimit),
-    ) {
-        Ok((status, answer_path.into()))
+    );
+
+    let log_path = stderr_log_path(outdir, target);
+    if stderr_path.metadata()?.len() > 0 {
+        create_dir_all(log_path.parent().unwrap())?;
+        copy(&stderr_path, &log_path)?;
+    } else if log_path.exists() {
+        remove_file(&log_path)?;
+    }
+
+    match result {
+        Ok(status) => {
+            if status.success() {
+                answer.persist(&answer_path)?;
+            } else if answer_path.exists() {
+                remove_file(&answer_path)?;
+            }
+            Ok((status, answer_path))
+        }
+        Err(_) if log_path.exists() => bail!("failed to run (stderr: {log_path:?})"),
+        Err(_) => bail!("failed to run"),
+    }
+}
+
+/// 解答の生成時の標準エラー出力の保存先 {outdir}/logs/{name}.err
+pub(crate) fn stderr_log_path(outdir: &Path, target: &Path) -> PathBuf {
+    outdir
+        .join("logs")
+        .join(format!("{}.err", input_name(target)))
+}
+
+/// target に対する解答の出力先
+fn answer_path(outdir: &Path, target: &Path) -> PathBuf {
+    outdir.join(format!("{}.ans", input_name(target)))
+}
+
+/// solver の解答の出力先（solver が複数の場合は outdir/{solver 名}）
+fn solver_outdir(outdir: &Path, solver: &Path, multiple: bool) -> PathBuf {
+    if multiple {
+        outdir.join(solver.file_stem().unwrap().to_str().unwrap())
     } else {
-        bail!("failed to run")
+        outdir.to_path_buf()
+    }
+}
+
+/// items の各要素に f を適用した結果を items の順に返す
+/// jobs 個のスレッドがまだ処理していない要素を順に取り出して処理する
+fn run_parallel<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let mut outputs: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..jobs.min(items.len()).max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outputs = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            break;
+                        };
+                        outputs.push((i, f(item)));
+                    }
+                    outputs
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    outputs.sort_by_key(|(i, _)| *i);
+    outputs.into_iter().map(|(_, output)| output).collect()
+}
+
+/// 全ての solver が解答を出力し，かつ内容が一致しないケースを返す
+/// answers[i][j] は i 番目のケースに対する j 番目の solver の解答
+fn differing_cases(
+    testcases: &[PathBuf],
+    answers: &[Vec<Option<PathBuf>>],
+) -> Result<Vec<PathBuf>> {
+    let mut differing = Vec::new();
+    for (target, answers) in testcases.iter().zip(answers) {
+        let Some(answers) = answers.iter().cloned().collect::<Option<Vec<_>>>() else {
+            continue;
+        };
+        let contents = answers
+            .iter()
+            .map(read)
+            .collect::<std::io::Result<Vec<_>>>()?;
+        if contents.windows(2).any(|pair| pair[0] != pair[1]) {
+            differing.push(target.clone());
+        }
     }
+    Ok(differing)
 }
 
 pub(super) fn root(args: SolveArgs) -> Result<()> {
     info!("{:#?}", args);
-    ensure!(args.solver.exists(), "solver {:?} not found", args.solver);
+    for solver in &args.solvers {
+        ensure!(solver.exists(), "solver {:?} not found", solver);
+    }
 
     let testcases = {
         let mut testcases = Vec::new();
-        for base in args.testcases {
-            let sub_files = find_files(&base, args.recursive).unwrap();
+        for base in &args.testcases {
+            let sub_files = find_files(base, args.recursive).unwrap();
 
             for target in sub_files {
-                if let Some(ext) = target.extension() {
-                    if ext == "in" {
-                        testcases.push(target);
-                    }
+                if is_input(&target) {
+                    testcases.push(target);
                 }
             }
         }
         testcases
     };
-    if testcases.len() == 0 {
+    if testcases.is_empty() {
         println!("no testcase found!");
         return Ok(());
     }
     info!("testcases = {testcases:#?}");
 
-    let langs = make_languages(&args.language)?;
+    let multiple = args.solvers.len() > 1;
 
-    if !args.outdir.exists() {
-        create_dir_all(&args.outdir)?;
+    // 既存の解答は --force を指定した場合のみ上書きする
+    if !args.skip_existing && !args.force {
+        let existing = args
+            .solvers
+            .iter()
+            .flat_map(|solver| {
+                let outdir = solver_outdir(&args.outdir, solver, multiple);
+                testcases
+                    .iter()
+                    .map(move |target| answer_path(&outdir, target))
+            })
+            .filter(|path| path.exists())
+            .count();
+        ensure!(
+            existing == 0,
+            "{existing} answer(s) already exist (use --skip-existing to keep them, or --force to overwrite them)"
+        );
     }
 
+    let langs = make_languages(&args.language)?;
+
     #[derive(Tabled)]
     struct Result {
         status: String,
         input: String,
         generated_answer: String,
     }
-    let mut results = Vec::new();
 
-    let dir = TempDir::new()?;
-    let runstep = compile_and_get_runstep(&dir, &args.solver, &langs)?;
-    let bar = ProgressBar::new(testcases.len() as u64);
-    bar.set_style(ProgressStyle::default_bar().template("[Solve] {bar} {pos:>4}/{len:4}")?);
-    for target in testcases {
-        match solve(&dir, &target, &args.outdir, &runstep, args.timelimit) {
-            Ok((status, answer)) => {
-                info!("[SOLVE] {:?}, status = {:?}", answer, status);
+    // answers[i][j] は i 番目のケースに対する j 番目の solver の解答（正常終了した場合のみ）
+    let mut answers: Vec<Vec<Option<PathBuf>>> = vec![Vec::new(); testcases.len()];
+    for (i, solver) in args.solvers.iter().enumerate() {
+        let outdir = solver_outdir(&args.outdir, solver, multiple);
+        if !outdir.exists() {
+            create_dir_all(&outdir)?;
+        }
 
-                results.push(Result {
-                    status: status.to_string(),
+        let mut results = Vec::new();
+        let dir = TempDir::new()?;
+        let runstep = compile_and_get_runstep(&dir, solver, &langs)?;
+        let bar = ProgressBar::new(testcases.len() as u64);
+        let label = if multiple {
+            solver.file_stem().unwrap().to_string_lossy().to_string()
+        } else {
+            "Solve".to_string()
+        };
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template(&format!("[{label}] {{bar}} {{pos:>4}}/{{len:4}}"))?,
+        );
+        let outcomes = run_parallel(&testcases, args.jobs as usize, |target| {
+            let existing = answer_path(&outdir, target);
+            if args.skip_existing && existing.exists() {
+                info!("[SKIP] {:?}", existing);
+                bar.inc(1);
+                let row = Result {
+                    status: "SKIPPED".to_string(),
                     input: format!("{:?}", target),
-                    generated_answer: format!("{:?}", answer),
-                });
-            }
-            Err(err) => {
-                warn!("[SOLVE] {:?}, reason = {:?}", target, err);
+                    generated_answer: format!("{:?}", existing),
+                };
+                return (Some(row), Some(existing));
             }
+
+            let outcome = match solve(&dir, target, &outdir, &runstep, args.timelimit) {
+                Ok((status, answer)) => {
+                    info!("[SOLVE] {:?}, status = {:?}", answer, status);
+                    let log_path = stderr_log_path(&outdir, target);
+                    if !status.success() && log_path.exists() {
+                        bar.suspend(|| println!("[STDERR] {target:?}: {status}, see {log_path:?}"));
+                    }
+
+                    let row = Result {
+                        status: status.to_string(),
+                        input: format!("{:?}", target),
+                        generated_answer: format!("{:?}", answer),
+                    };
+                    (Some(row), status.success().then_some(answer))
+                }
+                Err(err) => {
+                    warn!("[SOLVE] {:?}, reason = {:?}", target, err);
+                    (None, None)
+                }
+            };
+            bar.inc(1);
+            outcome
+        });
+        bar.finish();
+        for ((row, answer_path), answer_paths) in outcomes.into_iter().zip(answers.iter_mut()) {
+            results.extend(row);
+            answer_paths.push(answer_path);
+        }
+
+        if i > 0 {
+            println!();
+        }
+        if multiple {
+            println!("[{solver:?}]");
         }
-        bar.inc(1);
+        println!("{}", Table::new(results));
     }
-    bar.finish();
 
-    println!("{}", Table::new(results));
+    if multiple {
+        let differing = differing_cases(&testcases, &answers)?;
+        println!();
+        for target in &differing {
+            println!("[DIFF] {target:?}");
+        }
+        println!(
+            "the answers differ on {} of {} case(s)",
+            differing.len(),
+            testcases.len()
+        );
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    #[test]
+    fn test_solver_outdir() {
+        let outdir = Path::new("testcases/answer");
+        assert_eq!(
+            solver_outdir(outdir, Path::new("sol/a.cpp"), false),
+            PathBuf::from("testcases/answer")
+        );
+        assert_eq!(
+            solver_outdir(outdir, Path::new("sol/a.cpp"), true),
+            PathBuf::from("testcases/answer/a")
+        );
+    }
+
+    #[test]
+    fn test_answer_path() {
+        let outdir = Path::new("testcases/answer");
+        assert_eq!(
+            answer_path(outdir, Path::new("testcases/input/random_000.in")),
+            PathBuf::from("testcases/answer/random_000.ans")
+        );
+        assert_eq!(
+            answer_path(outdir, Path::new("testcases/input/random_000.in.gz")),
+            PathBuf::from("testcases/answer/random_000.ans")
+        );
+    }
+
+    #[test]
+    fn test_stderr_log_path() {
+        assert_eq!(
+            stderr_log_path(
+                Path::new("testcases/answer"),
+                Path::new("testcases/input/random_000.in.gz")
+            ),
+            PathBuf::from("testcases/answer/logs/random_000.err")
+        );
+    }
+
+    #[test]
+    fn test_solve() {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path().join("a.in");
+        write(&input, "1\n").unwrap();
+        let answer = dir.path().join("a.ans");
+        let sh = |script: &str| {
+            CommandStep::new("sh".to_string(), vec!["-c".to_string(), script.to_string()])
+        };
+
+        let (status, path) = solve(dir.path(), &input, dir.path(), &sh("cat"), 10.0).unwrap();
+        assert_eq!(status, ExecuteStatus::Success);
+        assert_eq!(path, answer);
+        assert_eq!(std::fs::read_to_string(&answer).unwrap(), "1\n");
+
+        // 異常終了した場合は途中までの解答も以前の解答も残さない
+        let (status, _) = solve(
+            dir.path(),
+            &input,
+            dir.path(),
+            &sh("echo partial; exit 1"),
+            10.0,
+        )
+        .unwrap();
+        assert_eq!(status, ExecuteStatus::Fail);
+        assert!(!answer.exists());
+    }
+
+    #[test]
+    fn test_run_parallel() {
+        let items: Vec<u32> = (0..100).collect();
+        let expected: Vec<u32> = items.iter().map(|x| x * 2).collect();
+        assert_eq!(run_parallel(&items, 1, |x| x * 2), expected);
+        assert_eq!(run_parallel(&items, 8, |x| x * 2), expected);
+        assert!(run_parallel(&Vec::<u32>::new(), 4, |x| x * 2).is_empty());
+    }
+
+    #[test]
+    fn test_differing_cases() {
+        let dir = TempDir::new().unwrap();
+        let path = |name: &str, content: &str| {
+            let path = dir.path().join(name);
+            write(&path, content).unwrap();
+            Some(path)
+        };
+        let testcases = vec![
+            PathBuf::from("a.in"),
+            PathBuf::from("b.in"),
+            PathBuf::from("c.in"),
+        ];
+        let answers = vec![
+            vec![path("a1.ans", "1\n"), path("a2.ans", "1\n")],
+            vec![path("b1.ans", "1\n"), path("b2.ans", "2\n")],
+            vec![path("c1.ans", "1\n"), None],
+        ];
+        assert_eq!(
+            differing_cases(&testcases, &answers).unwrap(),
+            vec![PathBuf::from("b.in")]
+        );
+    }
+}
@@ -1,8 +1,16 @@
 use crate::language::{default_languages, CustomLang, Language};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use regex::Regex;
-use std::fs;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, read, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub(crate) fn find_files(base: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
     let mut generators = Vec::new();
@@ -44,6 +52,142 @@ pub(crate) fn make_languages(
     }
 }
 
+/// glob パターン（`*` と `?` のみ対応）を全体一致の Regex に変換する
+pub(crate) fn glob_to_regex(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Ok(Regex::new(&regex)?)
+}
+
+/// name が filters のいずれかに一致し（filters が空なら常に一致），かつ excludes のいずれにも一致しないかどうか
+pub(crate) fn is_selected(name: &str, filters: &[Regex], excludes: &[Regex]) -> bool {
+    (filters.is_empty() || filters.iter().any(|re| re.is_match(name)))
+        && !excludes.iter().any(|re| re.is_match(name))
+}
+
+/// 再現可能な擬似乱数（splitmix64）
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// 現在時刻から seed を決める
+    pub(crate) fn from_time() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+        Self::new(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// seed から決まる順序で items をシャッフルする（Fisher-Yates）
+pub(crate) fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut rng = SplitMix64::new(seed);
+    for i in (1..items.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// 内容が完全に一致するファイルの組 (先に現れたファイル, 重複したファイル) を列挙する
+pub(crate) fn find_duplicates(paths: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut seen: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    let mut duplicates = Vec::new();
+    for path in paths {
+        let content = read(path)?;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+
+        let candidates = seen.entry(hasher.finish()).or_default();
+        // ハッシュの衝突に備えて内容も比較する
+        let mut original = None;
+        for candidate in candidates.iter() {
+            if read(candidate)? == content {
+                original = Some(*candidate);
+                break;
+            }
+        }
+        match original {
+            Some(original) => duplicates.push((original.clone(), path.clone())),
+            None => candidates.push(path),
+        }
+    }
+    Ok(duplicates)
+}
+
+/// テストケースの入力（.in または gzip 圧縮された .in.gz）かどうか
+pub(crate) fn is_input(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.ends_with(".in") || name.ends_with(".in.gz")
+}
+
+pub(crate) fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// 入力ファイル名から .in または .in.gz を除いた名前
+pub(crate) fn input_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = name.strip_suffix(".gz").unwrap_or(&name);
+    name.strip_suffix(".in").unwrap_or(name).to_string()
+}
+
+/// 入力ファイルの .in または .in.gz を ext に置き換えたパス（a.in.gz -> a.{ext}）
+pub(crate) fn input_with_extension(path: &Path, ext: &str) -> PathBuf {
+    path.with_file_name(format!("{}.{ext}", input_name(path)))
+}
+
+/// gzip 圧縮された入力は dir に {name}.in として展開してそのパスを返す．圧縮されていなければ path を返す
+pub(crate) fn inflate_input(path: &Path, dir: &Path) -> Result<PathBuf> {
+    if !is_gzip(path) {
+        return Ok(path.to_path_buf());
+    }
+
+    let inflated = dir.join(format!("{}.in", input_name(path)));
+    let mut decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+    io::copy(&mut decoder, &mut BufWriter::new(File::create(&inflated)?))
+        .with_context(|| format!("failed to decompress {path:?}"))?;
+    Ok(inflated)
+}
+
+/// 入力ファイルの（gzip 圧縮されていれば展開後の）大きさ
+pub(crate) fn input_size(path: &Path) -> Result<u64> {
+    if is_gzip(path) {
+        let mut decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+        Ok(io::copy(&mut decoder, &mut io::sink())?)
+    } else {
+        Ok(path.metadata()?.len())
+    }
+}
+
+/// src を gzip 圧縮して dst に書き込む
+pub(crate) fn gzip_file(src: &Path, dst: &Path) -> Result<()> {
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(dst)?), Compression::default());
+    io::copy(&mut BufReader::new(File::open(src)?), &mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,4 +219,104 @@ mod tests {
         let langs = make_languages(&vec!["invalid".to_string()]);
         assert!(langs.is_err());
     }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let re = glob_to_regex("random_*").unwrap();
+        assert!(re.is_match("random_000"));
+        assert!(!re.is_match("max_random_000"));
+
+        let re = glob_to_regex("case_?.in").unwrap();
+        assert!(re.is_match("case_1.in"));
+        assert!(!re.is_match("case_10.in"));
+        assert!(!re.is_match("case_1xin"));
+    }
+
+    #[test]
+    fn test_is_selected() {
+        let filters = vec![glob_to_regex("random_*").unwrap()];
+        let excludes = vec![glob_to_regex("*_001").unwrap()];
+
+        assert!(is_selected("random_000", &filters, &excludes));
+        assert!(!is_selected("random_001", &filters, &excludes));
+        assert!(!is_selected("max_000", &filters, &excludes));
+        assert!(is_selected("max_000", &[], &[]));
+        assert!(!is_selected("max_001", &[], &excludes));
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut a: Vec<_> = (0..10).collect();
+        let mut b = a.clone();
+        shuffle(&mut a, 42);
+        shuffle(&mut b, 42);
+        assert_eq!(a, b);
+        assert_ne!(a, (0..10).collect::<Vec<_>>());
+
+        let mut sorted = a.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+
+        let mut c: Vec<_> = (0..10).collect();
+        shuffle(&mut c, 43);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = ["a.in", "b.in", "c.in", "d.in"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        std::fs::write(&paths[0], "1 2\n").unwrap();
+        std::fs::write(&paths[1], "3 4\n").unwrap();
+        std::fs::write(&paths[2], "1 2\n").unwrap();
+        std::fs::write(&paths[3], "1 2\n").unwrap();
+
+        assert_eq!(
+            find_duplicates(&paths).unwrap(),
+            vec![
+                (paths[0].clone(), paths[2].clone()),
+                (paths[0].clone(), paths[3].clone())
+            ]
+        );
+        assert!(find_duplicates(&paths[..2]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_input_name() {
+        assert!(is_input(Path::new("dir/a.in")));
+        assert!(is_input(Path::new("dir/a.in.gz")));
+        assert!(!is_input(Path::new("dir/a.ans")));
+        assert!(!is_input(Path::new("dir/a.gz")));
+
+        assert_eq!(input_name(Path::new("dir/a.in")), "a");
+        assert_eq!(input_name(Path::new("dir/a.b.in.gz")), "a.b");
+        assert_eq!(
+            input_with_extension(Path::new("dir/a.in.gz"), "ans"),
+            PathBuf::from("dir/a.ans")
+        );
+        assert_eq!(
+            input_with_extension(Path::new("dir/a.in"), "tl"),
+            PathBuf::from("dir/a.tl")
+        );
+    }
+
+    #[test]
+    fn test_gzip() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("a.in");
+        let compressed = dir.path().join("b.in.gz");
+        fs::write(&plain, "1 2\n3\n").unwrap();
+        gzip_file(&plain, &compressed).unwrap();
+        assert_ne!(read(&compressed).unwrap(), read(&plain).unwrap());
+
+        let out = tempfile::tempdir().unwrap();
+        let inflated = inflate_input(&compressed, out.path()).unwrap();
+        assert_eq!(inflated, out.path().join("b.in"));
+        assert_eq!(fs::read_to_string(&inflated).unwrap(), "1 2\n3\n");
+        assert_eq!(inflate_input(&plain, out.path()).unwrap(), plain);
+        assert_eq!(input_size(&compressed).unwrap(), 6);
+    }
 }
@@ -1,23 +1,39 @@
+use crate::generate::manifest::source_hash;
+use crate::generate::schema;
+use crate::judge::read_tags;
 use crate::language::{compile_and_get_runstep, CommandStep, ExecuteStatus, Language};
-use crate::utils::{find_files, make_languages};
-use anyhow::{bail, Result};
-use clap::Args;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::utils::{
+    find_duplicates, find_files, inflate_input, input_name, is_input, make_languages, shuffle,
+    SplitMix64,
+};
+use anyhow::{bail, ensure, Context, Result};
+use clap::{Args, ValueEnum};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use log::{info, warn};
-use std::fs::{create_dir_all, File};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{copy, create_dir_all, read, read_to_string, remove_file, write, File};
+use std::io::{pipe, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::thread;
 use std::time::Duration;
-use tabled::{Table, Tabled};
+use tabled::{builder::Builder, Table, Tabled};
 use tempfile::TempDir;
 
+mod cache;
+mod forbid;
+mod lint;
+mod report;
+
 #[derive(Debug, Args)]
 pub(super) struct ValidateArgs {
     /// path to the validator
-    #[arg(value_name = "VALIDATOR", required = true)]
+    #[arg(value_name = "VALIDATOR", required_unless_present_any = ["forbid", "schema", "lint", "duplicates", "orphans", "name_pattern", "group_validator", "min_cases", "require_max"])]
     validators: Vec<PathBuf>,
 
-    /// recursively search for validator
+    /// recursively search for validators and testcases (e.g. subtask directories)
     #[arg(short, long, default_value_t = false)]
     recursive: bool,
 
@@ -25,7 +41,11 @@ pub(super) struct ValidateArgs {
     #[arg(short, long, default_value = "./testcases/input")]
     testcases: Vec<PathBuf>,
 
-    ///
+    /// extension of the files to validate, e.g. ans to run an output format validator over the answers
+    #[arg(long, value_name = "EXT", default_value = "in")]
+    ext: String,
+
+    /// output directory
     #[arg(short, long, default_value = "./testcases/validate")]
     outdir: PathBuf,
 
@@ -33,6 +53,131 @@ pub(super) struct ValidateArgs {
     #[arg(short, long, default_value_t = false)]
     quiet: bool,
 
+    /// save the error outputs of all cases (by default, only of the invalid cases and of the cases
+    /// on which the validator printed something)
+    #[arg(long, default_value_t = false, conflicts_with = "quiet")]
+    save_all: bool,
+
+    /// pass --group <GROUP> to the validator. GROUP is the tag group=GROUP of the case ({name}.tags)
+    /// or the name of its directory (subtaskN is passed as N)
+    #[arg(long, default_value_t = false)]
+    pass_group: bool,
+
+    /// pass --testset <TESTSET> to the validator (the tag testset=TESTSET of the case has higher priority)
+    #[arg(long, value_name = "TESTSET")]
+    testset: Option<String>,
+
+    /// validator applied only to the cases of GROUP (repeatable). The group of a case is the tag
+    /// group=GROUP ({name}.tags) or the name of its directory (subtaskN also matches N)
+    #[arg(long, value_name = "GROUP=VALIDATOR", value_parser = parse_group_validator)]
+    group_validator: Vec<(String, PathBuf)>,
+
+    /// constraint passed to the validators as the argument -KEY=VALUE and the environment variable KEY=VALUE
+    /// (repeatable, takes priority over --param-file)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_param)]
+    param: Vec<(String, String)>,
+
+    /// toml file of the constraints (KEY = VALUE) passed to the validators in the same way as --param
+    #[arg(long, value_name = "PATH")]
+    param_file: Option<PathBuf>,
+
+    /// require GROUP to contain at least N cases (repeatable)
+    #[arg(long, value_name = "GROUP=N", value_parser = parse_min_cases)]
+    min_cases: Vec<(String, usize)>,
+
+    /// require each group (those in --min-cases, or all groups if omitted) to contain a case tagged max
+    #[arg(long, default_value_t = false)]
+    require_max: bool,
+
+    /// exit with 0 even if some cases fail validation
+    #[arg(long, default_value_t = false)]
+    no_fail: bool,
+
+    /// toml file listing regexes ([[forbid]] pattern, message) that must not match any line of the cases
+    #[arg(long, value_name = "PATH")]
+    forbid: Option<PathBuf>,
+
+    /// run built-in formatting checks, e.g. --lint=crlf,empty (all but the numeric checks if no value is given)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "CHECK,...",
+        num_args = 0..=1,
+        require_equals = true,
+        value_delimiter = ','
+    )]
+    lint: Option<Vec<lint::Lint>>,
+
+    /// report cases whose content is identical to an earlier case (compared after decompressing .in.gz)
+    #[arg(long, default_value_t = false)]
+    duplicates: bool,
+
+    /// regex that the file name of every case must match, e.g. '^[a-z0-9_]+_\d{3}\.in$'
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    name_pattern: Option<Regex>,
+
+    /// report cases(*.in) without the answer(*.ans) of the same name in --answer-dir, and vice versa
+    #[arg(long, default_value_t = false)]
+    orphans: bool,
+
+    /// directory containing the answers(*.ans) for --orphans (searched recursively)
+    #[arg(long, value_name = "DIR", default_value = "./testcases/answer")]
+    answer_dir: PathBuf,
+
+    /// check the token structure and bounds of each case by kuroe itself.
+    /// A .schema file (the format of the schema generator) or a toml file with the schema in format
+    #[arg(long, value_name = "PATH")]
+    schema: Option<PathBuf>,
+
+    /// how to pass the case to the validator: stdin, or arg (the path of the case as the first argument)
+    #[arg(long, value_enum, default_value_t = InputVia::Stdin)]
+    input_via: InputVia,
+
+    /// timelimit of the validator
+    #[arg(visible_alias = "tl", long, default_value_t = 10.0)]
+    timelimit: f64,
+
+    /// skip running the validators on cases that passed before, unless the validator or the case has changed
+    /// (the results are recorded in OUTDIR/cache.json)
+    #[arg(long, default_value_t = false)]
+    cache: bool,
+
+    /// validate only the cases that failed in the last run (recorded in OUTDIR/failed.json)
+    #[arg(long, default_value_t = false)]
+    only_failed: bool,
+
+    /// validate only K cases chosen at random (reproducible with --seed)
+    #[arg(long, value_name = "K")]
+    sample: Option<usize>,
+
+    /// seed of --sample (decided from the current time and printed if omitted)
+    #[arg(long, requires = "sample")]
+    seed: Option<u64>,
+
+    /// stream the error output of the validator to the terminal while it runs
+    #[arg(short, long, default_value_t = false)]
+    verbose: bool,
+
+    /// maximum virtual memory of the validator in MiB (unlimited if omitted)
+    #[arg(long, value_name = "MiB")]
+    memory_limit: Option<u64>,
+
+    /// maximum size of the files the validator writes (including the error output) in MiB
+    #[arg(long, value_name = "MiB", default_value_t = 256)]
+    output_limit: u64,
+
+    /// validate policy
+    #[arg(short, long, value_enum, default_value_t = ValidatePolicy::All)]
+    policy: ValidatePolicy,
+
+    /// write a report (per check, per group and the failures) to PATH. HTML if PATH ends with .html, otherwise Markdown
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// output format of the results
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
     /// COMMAND[0:-1] are the compile commands. COMMAND[-1] is execute command
     #[arg(
         short,
@@ -44,61 +189,543 @@ pub(super) struct ValidateArgs {
     language: Vec<String>,
 }
 
-/// vaildate の結果とエラー出力先パスを返す
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum InputVia {
+    /// Standard input
+    Stdin,
+
+    /// Path of the case as the first argument (for validators that open or seek the file)
+    Arg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ValidatePolicy {
+    /// Run all checks on all cases anyway
+    All,
+
+    /// The first invalid case ends the validation
+    FailBreak,
+}
+
+impl ValidatePolicy {
+    /// 検査の結果が出た後に検証を打ち切るかどうか
+    fn should_break(&self, success: bool) -> bool {
+        match self {
+            ValidatePolicy::All => false,
+            ValidatePolicy::FailBreak => !success,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// Tables and messages for humans
+    Table,
+
+    /// A JSON document with the result of every check on every case
+    Json,
+}
+
+/// path が検証対象（拡張子が ext のファイル）かどうか
+/// ext が in の場合は gzip 圧縮された .in.gz も対象とする
+fn is_target(path: &Path, ext: &str) -> bool {
+    if ext == "in" {
+        is_input(path)
+    } else {
+        path.extension().is_some_and(|e| e == ext)
+    }
+}
+
+/// 検証対象のファイル名から拡張子を除いた名前
+fn target_name(path: &Path) -> String {
+    if is_input(path) {
+        input_name(path)
+    } else {
+        path.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn parse_group_validator(s: &str) -> Result<(String, PathBuf)> {
+    match s.split_once('=') {
+        Some((group, validator)) if !group.is_empty() && !validator.is_empty() => {
+            Ok((group.to_string(), PathBuf::from(validator)))
+        }
+        _ => bail!("expected GROUP=VALIDATOR, got {s:?}"),
+    }
+}
+
+fn parse_param(s: &str) -> Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => bail!("expected KEY=VALUE, got {s:?}"),
+    }
+}
+
+/// --param-file と --param の制約を合わせた一覧（同じ KEY は --param を優先する）
+fn load_params(
+    param_file: Option<&Path>,
+    params: &[(String, String)],
+) -> Result<Vec<(String, String)>> {
+    let mut merged: BTreeMap<String, String> = BTreeMap::new();
+    if let Some(path) = param_file {
+        let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+        let table: toml::Table =
+            toml::from_str(&content).with_context(|| format!("failed to parse {path:?}"))?;
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(value) => value,
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                    value.to_string()
+                }
+                value => bail!("invalid value {value} of {key:?} in {path:?}"),
+            };
+            merged.insert(key, value);
+        }
+    }
+    merged.extend(params.iter().cloned());
+    Ok(merged.into_iter().collect())
+}
+
+fn parse_min_cases(s: &str) -> Result<(String, usize)> {
+    match s.split_once('=') {
+        Some((group, count)) if !group.is_empty() => {
+            let count = count
+                .parse::<usize>()
+                .with_context(|| format!("invalid count {count:?}"))?;
+            Ok((group.to_string(), count))
+        }
+        _ => bail!("expected GROUP=N, got {s:?}"),
+    }
+}
+
+/// 1 つのグループのケース数の検査結果
+#[derive(Debug, Serialize, Tabled)]
+struct Coverage {
+    group: String,
+    cases: usize,
+    /// タグ max の付いたケースの数
+    max_cases: usize,
+    status: String,
+}
+
+/// 各グループのケース数（と max のケースの有無）を検査する
+fn check_coverage(
+    testcases: &[PathBuf],
+    min_cases: &[(String, usize)],
+    require_max: bool,
+) -> Result<Vec<Coverage>> {
+    let mut groups: Vec<(String, usize)> = min_cases.to_vec();
+    if groups.is_empty() {
+        for target in testcases {
+            if let Some(group) = case_group(target)? {
+                if groups.iter().all(|(g, _)| *g != group) {
+                    groups.push((group, 0));
+                }
+            }
+        }
+        groups.sort();
+    }
+
+    let mut coverages = Vec::new();
+    for (group, min) in groups {
+        let mut cases = 0;
+        let mut max_cases = 0;
+        for target in testcases {
+            if in_group(target, &group)? {
+                cases += 1;
+                if read_tags(target)?.iter().any(|tag| tag == "max") {
+                    max_cases += 1;
+                }
+            }
+        }
+
+        let mut problems = Vec::new();
+        if cases < min {
+            problems.push(format!("FAIL: fewer than {min} case(s)"));
+        }
+        if require_max && max_cases == 0 {
+            problems.push("FAIL: no max case".to_string());
+        }
+        coverages.push(Coverage {
+            group,
+            cases,
+            max_cases,
+            status: if problems.is_empty() {
+                "OK".to_string()
+            } else {
+                problems.join("\n")
+            },
+        });
+    }
+    Ok(coverages)
+}
+
+/// seed から決まる k 個のケースを，元の順序を保って選ぶ
+fn sample(testcases: Vec<PathBuf>, k: usize, seed: u64) -> Vec<PathBuf> {
+    let mut indices: Vec<usize> = (0..testcases.len()).collect();
+    shuffle(&mut indices, seed);
+    let chosen: HashSet<usize> = indices.into_iter().take(k).collect();
+    testcases
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| chosen.contains(i))
+        .map(|(_, target)| target)
+        .collect()
+}
+
+/// 内容（.in.gz は展開後）が完全に一致するケースの組 (先に現れたケース, 重複したケース) を列挙する
+fn find_duplicate_cases(testcases: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let inflate_dir = TempDir::new()?;
+    let mut inflated = Vec::new();
+    for (i, target) in testcases.iter().enumerate() {
+        // 別のディレクトリの同名のケースと衝突しないよう，ケースごとに展開先を分ける
+        let dir = inflate_dir.path().join(i.to_string());
+        create_dir_all(&dir)?;
+        inflated.push(inflate_input(target, &dir)?);
+    }
+
+    let original = |path: &PathBuf| {
+        let i = inflated.iter().position(|p| p == path).unwrap();
+        testcases[i].clone()
+    };
+    Ok(find_duplicates(&inflated)?
+        .iter()
+        .map(|(first, duplicate)| (original(first), original(duplicate)))
+        .collect())
+}
+
+/// 同じ名前の .ans がない .in と，同じ名前の .in がない .ans を返す
+fn find_orphans(inputs: &[PathBuf], answers: &[PathBuf]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let input_names: HashSet<String> = inputs.iter().map(|input| input_name(input)).collect();
+    let answer_names: HashSet<String> = answers.iter().map(|answer| target_name(answer)).collect();
+    let orphan_inputs = inputs
+        .iter()
+        .filter(|input| !answer_names.contains(&input_name(input)))
+        .cloned()
+        .collect();
+    let orphan_answers = answers
+        .iter()
+        .filter(|answer| !input_names.contains(&target_name(answer)))
+        .cloned()
+        .collect();
+    (orphan_inputs, orphan_answers)
+}
+
+/// ディレクトリごとのケース数（ディレクトリ名順）
+fn count_by_dir(testcases: &[PathBuf]) -> Vec<(PathBuf, usize)> {
+    let mut counts: BTreeMap<PathBuf, usize> = BTreeMap::new();
+    for target in testcases {
+        let dir = target.parent().unwrap_or(Path::new("")).to_path_buf();
+        *counts.entry(dir).or_default() += 1;
+    }
+    counts.into_iter().collect()
+}
+
+/// 前回の検証で不正と判定されたケースの一覧を記録する outdir/failed.json
+fn failed_path(outdir: &Path) -> PathBuf {
+    outdir.join("failed.json")
+}
+
+/// 前回の検証で不正と判定されたケースの一覧（記録がなければ None）
+fn load_failed(outdir: &Path) -> Result<Option<Vec<PathBuf>>> {
+    let path = failed_path(outdir);
+    match read_to_string(&path) {
+        Ok(content) => Ok(Some(
+            serde_json::from_str(&content).with_context(|| format!("failed to parse {path:?}"))?,
+        )),
+        Err(_) => Ok(None),
+    }
+}
+
+fn save_failed(outdir: &Path, failed: &[PathBuf]) -> Result<()> {
+    create_dir_all(outdir)?;
+    write(
+        failed_path(outdir),
+        serde_json::to_string_pretty(failed)? + "\n",
+    )?;
+    Ok(())
+}
+
+/// 前回の記録のうち今回検査したケースを今回の結果で置き換える
+/// --sample や --only-failed で一部だけ検査した場合も，検査していないケースの記録は残す
+fn merge_failed(
+    previous: Vec<PathBuf>,
+    checked: &HashSet<&PathBuf>,
+    failed: &HashSet<&PathBuf>,
+) -> Vec<PathBuf> {
+    let mut merged: Vec<PathBuf> = previous
+        .into_iter()
+        .filter(|case| !checked.contains(case))
+        .chain(failed.iter().map(|case| case.to_path_buf()))
+        .collect();
+    merged.sort();
+    merged.dedup();
+    merged
+}
+
+/// {name}.tags の key=VALUE の VALUE
+fn read_tag(target: &Path, key: &str) -> Result<Option<String>> {
+    Ok(read_tags(target)?
+        .iter()
+        .find_map(|tag| tag.strip_prefix(key)?.strip_prefix('='))
+        .map(String::from))
+}
+
+/// ケースのグループ（タグ group=GROUP，なければケースのディレクトリ名（subtaskN なら N））
+fn case_group(target: &Path) -> Result<Option<String>> {
+    if let Some(group) = read_tag(target, "group")? {
+        return Ok(Some(group));
+    }
+    let Some(dir) = target.parent().and_then(|dir| dir.file_name()) else {
+        return Ok(None);
+    };
+    let dir = dir.to_string_lossy().to_string();
+    Ok(match dir.strip_prefix("subtask") {
+        Some(n) if n.parse::<u32>().is_ok() => Some(n.to_string()),
+        _ => Some(dir),
+    })
+}
+
+/// ケースが group に属するかどうか（subtaskN は N と同じグループとみなす）
+fn in_group(target: &Path, group: &str) -> Result<bool> {
+    let group = group.strip_prefix("subtask").unwrap_or(group);
+    Ok(case_group(target)?.is_some_and(|g| g == group))
+}
+
+/// testlib の検証器に渡す --group, --testset
+/// testset はタグ（{name}.tags の testset=TESTSET）を優先する
+fn context_args(target: &Path, pass_group: bool, testset: Option<&str>) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    if pass_group {
+        if let Some(group) = case_group(target)? {
+            args.extend(["--group".to_string(), group]);
+        }
+    }
+    if let Some(testset) = read_tag(target, "testset")?.or(testset.map(String::from)) {
+        args.extend(["--testset".to_string(), testset]);
+    }
+    Ok(args)
+}
+
+/// 1 つのケースに対する 1 つの検査（検証器，--forbid，--schema）の結果
+#[derive(Debug, Serialize)]
+struct CaseResult {
+    case: PathBuf,
+    /// 検証器のパス，または "forbid", "schema"
+    validator: String,
+    /// --group-validator のグループ
+    group: Option<String>,
+    status: String,
+    success: bool,
+    /// --cache により検証器の実行を省いたかどうか
+    cached: bool,
+    /// 検証器のエラー出力の保存先
+    stderr: Option<PathBuf>,
+    message: Option<String>,
+}
+
+impl CaseResult {
+    fn new(case: &Path, validator: &str, group: Option<&str>, success: bool) -> Self {
+        CaseResult {
+            case: case.to_path_buf(),
+            validator: validator.to_string(),
+            group: group.map(String::from),
+            status: if success { "OK" } else { "FAIL" }.to_string(),
+            success,
+            cached: false,
+            stderr: None,
+            message: None,
+        }
+    }
+}
+
+/// target の検証器のエラー出力の保存先
+/// testcases のいずれかの下にある場合はそこからの相対パスを outdir 以下に再現する
+fn val_path(outdir: &Path, target: &Path, testcases: &[PathBuf]) -> PathBuf {
+    let subdir = testcases
+        .iter()
+        .filter_map(|base| target.strip_prefix(base).ok())
+        .find(|relative| !relative.as_os_str().is_empty())
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""));
+    outdir
+        .join(subdir)
+        .join(format!("{}.val", target_name(target)))
+}
+
+/// vaildate の結果，検証器を終了させたシグナル，エラー出力先パス，エラー出力を返す
 fn validate<P: AsRef<Path>>(
     current_dir: P,
     target: &Path,
     outdir: &Path,
     run: &CommandStep,
-    quiet: bool,
-) -> Result<(ExecuteStatus, Option<PathBuf>)> {
-    let input = File::open(&target)?;
-    let name = target.file_stem().unwrap().to_string_lossy().to_string();
+    args: &ValidateArgs,
+    context: Vec<String>,
+    bar: &ProgressBar,
+) -> Result<(ExecuteStatus, Option<i32>, Option<PathBuf>, String)> {
+    // gzip 圧縮された入力は展開してから渡す
+    let inflate_dir = TempDir::new()?;
+    let input_path = inflate_input(target, inflate_dir.path())?;
+    let (input, context): (Stdio, _) = match args.input_via {
+        InputVia::Stdin => (File::open(&input_path)?.into(), context),
+        InputVia::Arg => {
+            let path = input_path.canonicalize()?.to_string_lossy().to_string();
+            (Stdio::null(), [vec![path], context].concat())
+        }
+    };
+    let name = target_name(target);
 
-    if quiet {
-        if let Ok(status) = run.execute(
+    // エラー出力は一時ファイルに書き込み，保存するものだけを outdir にコピーする
+    let err_path = inflate_dir.path().join(format!("{name}.val"));
+    let err = File::create(&err_path)?;
+    let timelimit = Duration::from_secs_f64(args.timelimit);
+
+    let status = if args.verbose {
+        // エラー出力を .val に書き込みつつ，行ごとに端末へ流す
+        let (reader, writer) = pipe()?;
+        let bar = bar.clone();
+        let label = format!("{target:?}");
+        let streamer = thread::spawn(move || -> std::io::Result<()> {
+            let mut err = err;
+            for line in BufReader::new(reader).split(b'\n') {
+                let line = line?;
+                err.write_all(&line)?;
+                err.write_all(b"\n")?;
+                bar.suspend(|| eprintln!("[{label}] {}", String::from_utf8_lossy(&line)));
+            }
+            Ok(())
+        });
+        let status = run.execute_with_detail(
             current_dir,
-            Vec::new(),
+            context,
             input,
             Stdio::null(),
-            Stdio::null(),
-            Duration::from_secs(10),
-        ) {
-            Ok((status, None))
-        } else {
-            bail!("failed to run")
+            writer,
+            timelimit,
+        );
+        if let Err(err) = streamer.join().unwrap() {
+            warn!("[VALIDATE] failed to stream the error output: {err:?}");
         }
+        status
     } else {
-        let err_path = outdir.join(format!("{name}.val"));
-        let err = File::create(&err_path)?;
+        run.execute_with_detail(current_dir, context, input, Stdio::null(), err, timelimit)
+    };
 
-        if let Ok(status) = run.execute(
-            current_dir,
-            Vec::new(),
-            input,
-            Stdio::null(),
-            err,
-            Duration::from_secs(10),
-        ) {
-            Ok((status, Some(err_path.into())))
+    if let Ok((status, detail)) = status {
+        let stderr = read_to_string(&err_path).unwrap_or_default();
+
+        // --save-all でなければ，不正なケースか何か出力したケースのみ保存する
+        let save_path = val_path(outdir, target, &args.testcases);
+        let saved = if args.quiet {
+            None
+        } else if args.save_all || !status.success() || !stderr.is_empty() {
+            create_dir_all(save_path.parent().unwrap())?;
+            copy(&err_path, &save_path)?;
+            Some(save_path)
         } else {
-            bail!("failed to run")
+            // 以前の実行で保存したエラー出力を残さない
+            if save_path.exists() {
+                remove_file(&save_path)?;
+            }
+            None
+        };
+        Ok((status, detail.signal, saved, stderr))
+    } else {
+        bail!("failed to run")
+    }
+}
+
+/// 検証器が異常終了した（シグナルで終了した，資源の上限を超えた，または実行できなかった）ケースの status
+const VALIDATOR_ERROR: &str = "VALIDATOR ERROR";
+
+/// メモリの確保に失敗した際のエラー出力（C++ の std::bad_alloc, Python の MemoryError など）
+fn is_out_of_memory(stderr: &str) -> bool {
+    ["bad_alloc", "MemoryError", "out of memory"]
+        .iter()
+        .any(|marker| stderr.contains(marker))
+}
+
+/// ケースではなく検証器の不具合とみなす終了（シグナル，TLE, OLE, メモリ不足）であればその理由を返す
+/// メモリ不足は --memory-limit を指定した場合のみエラー出力から判断する
+fn validator_error(
+    status: ExecuteStatus,
+    signal: Option<i32>,
+    stderr: &str,
+    memory_limited: bool,
+) -> Option<String> {
+    match (status, signal) {
+        (ExecuteStatus::TimeLimitExceed, _) => Some("time limit exceeded".to_string()),
+        (ExecuteStatus::OutputLimitExceed, _) => Some("output limit exceeded".to_string()),
+        (ExecuteStatus::Fail, Some(signal)) => Some(format!("killed by {}", signal_name(signal))),
+        (ExecuteStatus::Fail, None) if memory_limited && is_out_of_memory(stderr) => {
+            Some("memory limit exceeded".to_string())
         }
+        _ => None,
     }
 }
 
+fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGSEGV => "SIGSEGV",
+        libc::SIGABRT => "SIGABRT",
+        libc::SIGFPE => "SIGFPE",
+        libc::SIGBUS => "SIGBUS",
+        libc::SIGILL => "SIGILL",
+        libc::SIGKILL => "SIGKILL",
+        _ => return format!("signal {signal}"),
+    };
+    format!("signal {signal} ({name})")
+}
+
+/// 表に表示する入力の行の最大文字数
+const SNIPPET_WIDTH: usize = 40;
+
+/// 検証器のメッセージ（testlib の "(stdin, line 3)" など）が指す入力の行を "line N: ..." として返す
+fn input_snippet(target: &Path, message: &str) -> Result<Option<String>> {
+    let re = Regex::new(r"\bline (\d+)").unwrap();
+    let Some(line) = re
+        .captures(message)
+        .and_then(|caps| caps[1].parse::<usize>().ok())
+    else {
+        return Ok(None);
+    };
+
+    let inflate_dir = TempDir::new()?;
+    let content = read(inflate_input(target, inflate_dir.path())?)?;
+    let content = String::from_utf8_lossy(&content);
+    let Some(text) = content.split('\n').nth(line.saturating_sub(1)) else {
+        return Ok(Some(format!("line {line}: <EOF>")));
+    };
+
+    let mut snippet: String = text.chars().take(SNIPPET_WIDTH).collect();
+    if text.chars().count() > SNIPPET_WIDTH {
+        snippet += "...";
+    }
+    Ok(Some(format!("line {line}: {snippet:?}")))
+}
+
+/// validator で testcases を検証した結果を返す
 fn validate_root(
     validator: &Path,
-    testcases: &Vec<PathBuf>,
+    testcases: &[PathBuf],
     langs: &Vec<Box<dyn Language>>,
-    outdir: &Path,
-    quiet: bool,
-) -> Result<()> {
+    args: &ValidateArgs,
+    group: Option<&str>,
+    params: &[(String, String)],
+    mut cache: Option<&mut cache::Cache>,
+) -> Result<Vec<CaseResult>> {
     let dir = TempDir::new()?;
-    let runstep = compile_and_get_runstep(&dir, validator, langs)?;
+    // 全てのケースがキャッシュされている場合はコンパイルも省く
+    let mut runstep = None;
+    let validator_hash = source_hash(validator)?;
 
-    let outdir = outdir.join(validator.file_stem().unwrap().to_str().unwrap());
-    if !quiet && !outdir.exists() {
+    let outdir = args
+        .outdir
+        .join(validator.file_stem().unwrap().to_str().unwrap());
+    if !args.quiet && !outdir.exists() {
         create_dir_all(&outdir)?;
     }
 
@@ -107,36 +734,207 @@ fn validate_root(
         ProgressStyle::default_bar()
             .template(&format!("[{validator:?}] {{bar}} {{pos:>4}}/{{len:4}}"))?,
     );
-    if quiet {
-        #[derive(Tabled)]
-        struct Result {
-            status: String,
-            target: String,
+    if args.format == OutputFormat::Json {
+        bar.set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    let validator_name = validator.to_string_lossy();
+    let mut results = Vec::new();
+    for target in testcases {
+        let mut context = context_args(target, args.pass_group, args.testset.as_deref())?;
+        context.extend(params.iter().map(|(key, value)| format!("-{key}={value}")));
+        let key = match cache {
+            Some(_) => {
+                let mut key_args = vec![format!("{:?}", args.input_via)];
+                key_args.extend(context.iter().cloned());
+                Some(cache::Cache::key(&validator_hash, target, &key_args)?)
+            }
+            None => None,
+        };
+        if let (Some(cache), Some(key)) = (cache.as_deref(), &key) {
+            if cache.contains(key) {
+                info!("[VALIDATE] target = {:?}: cached", target);
+                let mut result = CaseResult::new(target, &validator_name, group, true);
+                result.cached = true;
+                results.push(result);
+                bar.inc(1);
+                continue;
+            }
         }
-        let mut results = Vec::new();
 
-        for target in testcases {
-            match validate(&dir, target, &outdir, &runstep, quiet) {
-                Ok((status, None)) => {
-                    info!("[VALIDATE] target = {:?}: status = {:?}", target, status);
-
-                    results.push(Result {
-                        status: status.to_string(),
-                        target: format!("{:?}", target),
-                    });
-                }
-                Err(err) => {
-                    warn!("[VALIDATE] reason = {:?}", err);
+        if runstep.is_none() {
+            let mut step = compile_and_get_runstep(&dir, validator, langs)?
+                .output_limit(args.output_limit * 1024 * 1024);
+            if let Some(limit) = args.memory_limit {
+                step = step.memory_limit(limit * 1024 * 1024);
+            }
+            runstep = Some(
+                params
+                    .iter()
+                    .fold(step, |step, (key, value)| step.env(key, value)),
+            );
+        }
+        match validate(
+            &dir,
+            target,
+            &outdir,
+            runstep.as_ref().unwrap(),
+            args,
+            context,
+            &bar,
+        ) {
+            Ok((status, signal, saved, stderr)) => {
+                info!(
+                    "[VALIDATE] target = {:?}: output = {:?}, status = {:?}, signal = {:?}",
+                    target, saved, status, signal
+                );
+
+                let first_line = stderr.lines().next().unwrap_or_default().to_string();
+                let mut result = CaseResult::new(target, &validator_name, group, status.success());
+                result.status = status.to_string();
+                result.stderr = saved;
+                if let Some(reason) =
+                    validator_error(status, signal, &stderr, args.memory_limit.is_some())
+                {
+                    result.status = VALIDATOR_ERROR.to_string();
+                    let message: Vec<String> = [reason]
+                        .into_iter()
+                        .chain((!first_line.is_empty()).then_some(first_line))
+                        .collect();
+                    result.message = Some(message.join("\n"));
+                } else if !status.success() {
+                    let snippet = input_snippet(target, &first_line)?;
+                    let message: Vec<String> = [first_line].into_iter().chain(snippet).collect();
+                    result.message = Some(message.join("\n"));
                 }
-                _ => {
-                    unreachable!();
+                if let (Some(cache), Some(key), true) = (cache.as_deref_mut(), key, result.success)
+                {
+                    cache.insert(key);
                 }
+                results.push(result);
             }
-            bar.inc(1);
+            Err(err) => {
+                warn!("[VALIDATE] reason = {:?}", err);
+                let mut result = CaseResult::new(target, &validator_name, group, false);
+                result.status = VALIDATOR_ERROR.to_string();
+                result.message = Some(format!("{err:#}"));
+                results.push(result);
+            }
+        }
+        bar.inc(1);
+
+        if results
+            .last()
+            .is_some_and(|result| args.policy.should_break(result.success))
+        {
+            break;
         }
-        bar.finish();
+    }
+    bar.finish();
+
+    Ok(results)
+}
 
-        println!("{}", Table::new(results));
+fn status_label(result: &CaseResult) -> String {
+    if result.cached {
+        format!("{} (cached)", result.status)
+    } else {
+        result.status.clone()
+    }
+}
+
+/// 検査（検証器，forbid など）ごとの集計
+#[derive(Debug, PartialEq, Serialize, Tabled)]
+struct Summary {
+    check: String,
+    passed: usize,
+    failed: usize,
+}
+
+/// results を検査ごとに集計する（検査の順序は results での初出順）
+fn summarize(results: &[CaseResult]) -> Vec<Summary> {
+    let mut summaries: Vec<Summary> = Vec::new();
+    for result in results {
+        let check = match result.group {
+            Some(ref group) => format!("{} (group {group})", result.validator),
+            None => result.validator.clone(),
+        };
+        let index = match summaries.iter().position(|summary| summary.check == check) {
+            Some(index) => index,
+            None => {
+                summaries.push(Summary {
+                    check,
+                    passed: 0,
+                    failed: 0,
+                });
+                summaries.len() - 1
+            }
+        };
+        if result.success {
+            summaries[index].passed += 1;
+        } else {
+            summaries[index].failed += 1;
+        }
+    }
+    summaries
+}
+
+/// 同じ検査で同じ理由により不正と判定されたケースのまとまり
+#[derive(Debug, PartialEq, Serialize, Tabled)]
+struct FailureCluster {
+    check: String,
+    /// メッセージの先頭行（数値は # に置き換える）
+    message: String,
+    cases: usize,
+    /// まとまりに含まれる最初のケース
+    example: String,
+}
+
+/// 不正と判定された結果を (検査, メッセージ) でまとめ，ケース数の多い順に返す
+/// 値や行番号だけが異なるメッセージを同じ理由とみなすため，メッセージ中の数値は # に置き換える
+fn cluster_failures(results: &[CaseResult]) -> Vec<FailureCluster> {
+    let number = Regex::new(r"\d+(\.\d+)?").unwrap();
+    let mut clusters: Vec<FailureCluster> = Vec::new();
+    for result in results.iter().filter(|result| !result.success) {
+        let first_line = result
+            .message
+            .as_deref()
+            .and_then(|message| message.lines().next())
+            .unwrap_or(&result.status);
+        let message = number.replace_all(first_line, "#").to_string();
+        match clusters
+            .iter_mut()
+            .find(|cluster| cluster.check == result.validator && cluster.message == message)
+        {
+            Some(cluster) => cluster.cases += 1,
+            None => clusters.push(FailureCluster {
+                check: result.validator.clone(),
+                message,
+                cases: 1,
+                example: format!("{:?}", result.case),
+            }),
+        }
+    }
+    // 安定ソートのため，同じケース数のまとまりは初出順のまま
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.cases));
+    clusters
+}
+
+/// 検証器ごとの結果の表を表示する（--quiet の場合はエラー出力の列を省く）
+fn print_results(results: &[CaseResult], quiet: bool) {
+    if quiet {
+        #[derive(Tabled)]
+        struct Result {
+            status: String,
+            target: String,
+            message: String,
+        }
+        let rows = results.iter().map(|result| Result {
+            status: status_label(result),
+            target: format!("{:?}", result.case),
+            message: result.message.clone().unwrap_or_default(),
+        });
+        println!("{}", Table::new(rows));
     } else {
         #[derive(Tabled)]
         struct Result {
@@ -144,37 +942,64 @@ fn validate_root(
             target: String,
             stderr: String,
         }
-        let mut results = Vec::new();
+        let rows = results.iter().map(|result| Result {
+            status: status_label(result),
+            target: format!("{:?}", result.case),
+            stderr: match (&result.stderr, &result.message) {
+                (Some(path), Some(message)) => format!("{path:?}\n{message}"),
+                (Some(path), None) => format!("{path:?}"),
+                (None, message) => message.clone().unwrap_or_default(),
+            },
+        });
+        println!("{}", Table::new(rows));
+    }
+}
 
-        for target in testcases {
-            match validate(&dir, target, &outdir, &runstep, quiet) {
-                Ok((status, Some(path))) => {
-                    info!(
-                        "[VALIDATE] target = {:?}: output = {:?}, status = {:?}",
-                        target, path, status
-                    );
-
-                    results.push(Result {
-                        status: status.to_string(),
-                        target: format!("{:?}", target),
-                        stderr: format!("{:?}", path),
-                    });
-                }
-                Err(err) => {
-                    warn!("[VALIDATE] reason = {:?}", err);
-                }
-                _ => {
-                    unreachable!();
-                }
-            }
-            bar.inc(1);
-        }
-        bar.finish();
+/// 行をテストケース，列を検証器とした表を出力し，続けて不正と判定された理由を出力する
+/// --policy fail-break で打ち切られ，実行されなかった組は - とする
+fn print_matrix(validators: &[PathBuf], testcases: &[PathBuf], results: &[CaseResult]) {
+    let names: Vec<String> = validators
+        .iter()
+        .map(|validator| validator.to_string_lossy().to_string())
+        .collect();
+    let find = |case: &PathBuf, name: &str| {
+        results
+            .iter()
+            .find(|result| result.case == *case && result.validator == name)
+    };
 
-        println!("{}", Table::new(results));
+    let mut builder = Builder::default();
+    let mut header = vec!["target".to_string()];
+    header.extend(
+        validators
+            .iter()
+            .map(|validator| validator.file_stem().unwrap().to_string_lossy().to_string()),
+    );
+    builder.push_record(header);
+    for case in testcases {
+        let mut row = vec![format!("{case:?}")];
+        for name in &names {
+            row.push(find(case, name).map_or("-".to_string(), status_label));
+        }
+        builder.push_record(row);
     }
+    println!("{}", builder.build());
 
-    Ok(())
+    for case in testcases {
+        for name in &names {
+            let Some(result) = find(case, name).filter(|result| !result.success) else {
+                continue;
+            };
+            let mut line = format!("[{}] {name} {case:?}", result.status);
+            if let Some(ref message) = result.message {
+                line += &format!(": {}", message.replace('\n', " "));
+            }
+            if let Some(ref stderr) = result.stderr {
+                line += &format!(" ({stderr:?})");
+            }
+            println!("{line}");
+        }
+    }
 }
 
 pub(super) fn root(args: ValidateArgs) -> Result<()> {
@@ -182,49 +1007,906 @@ pub(super) fn root(args: ValidateArgs) -> Result<()> {
 
     let validators = {
         let mut validators = Vec::new();
-        for base in args.validators {
-            for file in find_files(&base, args.recursive)? {
+        for base in &args.validators {
+            for file in find_files(base, args.recursive)? {
                 validators.push(file);
             }
         }
         validators
     };
-    if validators.len() == 0 {
+    if validators.is_empty()
+        && args.group_validator.is_empty()
+        && args.forbid.is_none()
+        && args.schema.is_none()
+        && args.lint.is_none()
+        && !args.duplicates
+        && !args.orphans
+        && args.name_pattern.is_none()
+        && args.min_cases.is_empty()
+        && !args.require_max
+    {
         println!("no validator found!");
         return Ok(());
     }
     info!("validators = {validators:#?}");
 
-    let testcases = {
+    // --only-failed, --sample で絞り込む前の全てのケース
+    let all_testcases = {
         let mut testcases = Vec::new();
-        for base in args.testcases {
-            let sub_files = find_files(&base, false)?;
+        for base in &args.testcases {
+            let sub_files = find_files(base, args.recursive)?;
 
             for target in sub_files {
-                if let Some(ext) = target.extension() {
-                    if ext == "in" {
-                        testcases.push(target);
-                    }
+                if is_target(&target, &args.ext) {
+                    testcases.push(target);
                 }
             }
         }
         testcases
     };
-    if testcases.len() == 0 {
-        println!("no testcase found!");
+    let testcases = if args.only_failed {
+        let Some(failed) = load_failed(&args.outdir)? else {
+            bail!(
+                "{:?} not found (run kuroe validate without --only-failed first)",
+                failed_path(&args.outdir)
+            );
+        };
+        all_testcases
+            .iter()
+            .filter(|target| failed.contains(target))
+            .cloned()
+            .collect()
+    } else {
+        all_testcases.clone()
+    };
+    let testcases = match args.sample {
+        Some(k) if k < testcases.len() => {
+            let seed = args
+                .seed
+                .unwrap_or_else(|| SplitMix64::from_time().next_u64());
+            // 同じ部分集合を再現できるよう seed を表示する
+            if args.format == OutputFormat::Table {
+                println!("sample seed = {seed}");
+            }
+            sample(testcases, k, seed)
+        }
+        _ => testcases,
+    };
+    if testcases.is_empty() {
+        if args.only_failed {
+            println!("no previously failed testcase found!");
+        } else {
+            println!("no testcase found!");
+        }
         return Ok(());
     }
     info!("testcases = {testcases:#?}");
+    if args.format == OutputFormat::Table {
+        for (dir, count) in count_by_dir(&testcases) {
+            println!("{count} case(s) found in {dir:?}");
+        }
+    }
 
     let langs = make_languages(&args.language)?;
+    let params = load_params(args.param_file.as_deref(), &args.param)?;
+    let json = args.format == OutputFormat::Json;
+    let mut cache = if args.cache {
+        Some(cache::Cache::load(&args.outdir)?)
+    } else {
+        None
+    };
+
+    // 全ての検査の結果
+    let mut all_results: Vec<CaseResult> = Vec::new();
+    // ポリシーにより以降の検査を打ち切るかどうか
+    let stopped = |results: &[CaseResult]| {
+        results
+            .iter()
+            .any(|result| args.policy.should_break(result.success))
+    };
+    for validator in &validators {
+        let results = validate_root(
+            validator,
+            &testcases,
+            &langs,
+            &args,
+            None,
+            &params,
+            cache.as_mut(),
+        )?;
+        all_results.extend(results);
+        if stopped(&all_results) {
+            break;
+        }
+    }
+    if !json {
+        match validators.len() {
+            0 => {}
+            1 => print_results(&all_results, args.quiet),
+            _ => print_matrix(&validators, &testcases, &all_results),
+        }
+    }
 
-    for (i, validator) in validators.iter().enumerate() {
-        validate_root(&validator, &testcases, &langs, &args.outdir, args.quiet)?;
+    // 小課題ごとの検証器は，そのグループのケースのみを検証する
+    #[derive(Tabled)]
+    struct GroupResult {
+        group: String,
+        validator: String,
+        cases: usize,
+        failed: usize,
+    }
+    let mut group_results = Vec::new();
+    for (group, validator) in &args.group_validator {
+        if stopped(&all_results) {
+            break;
+        }
+        ensure!(validator.exists(), "validator {validator:?} not found");
+        let mut group_cases = Vec::new();
+        for target in &testcases {
+            if in_group(target, group)? {
+                group_cases.push(target.clone());
+            }
+        }
 
-        if i + 1 < validators.len() {
-            println!("");
+        let results = if group_cases.is_empty() {
+            Vec::new()
+        } else {
+            validate_root(
+                validator,
+                &group_cases,
+                &langs,
+                &args,
+                Some(group),
+                &params,
+                cache.as_mut(),
+            )?
+        };
+        if !json {
+            if !validators.is_empty() || !group_results.is_empty() {
+                println!();
+            }
+            if group_cases.is_empty() {
+                println!("no testcase found in group {group:?}!");
+            } else {
+                print_results(&results, args.quiet);
+            }
         }
+        group_results.push(GroupResult {
+            group: group.clone(),
+            validator: format!("{validator:?}"),
+            cases: group_cases.len(),
+            failed: results.iter().filter(|result| !result.success).count(),
+        });
+        all_results.extend(results);
     }
+    if !json && !group_results.is_empty() {
+        println!();
+        println!("{}", Table::new(group_results));
+    }
+
+    if let Some(forbid) = args.forbid.as_ref().filter(|_| !stopped(&all_results)) {
+        let patterns = forbid::load_patterns(forbid)?;
+        if !json && (!validators.is_empty() || !args.group_validator.is_empty()) {
+            println!();
+        }
+
+        let mut violated = 0;
+        for target in &testcases {
+            let violations = forbid::check_case(target, &patterns)?;
+            let mut result = CaseResult::new(target, "forbid", None, violations.is_empty());
+            if !violations.is_empty() {
+                violated += 1;
+                result.message = Some(violations.join("\n"));
+            }
+            if !json {
+                for violation in &violations {
+                    println!("[FORBID] {target:?}: {violation}");
+                }
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{violated} of {} case(s) contain forbidden patterns",
+                testcases.len()
+            );
+        }
+    }
+
+    if let Some(schema_path) = args.schema.as_ref().filter(|_| !stopped(&all_results)) {
+        let schema = schema::load_schema(schema_path)?;
+        if !json
+            && (!validators.is_empty() || !args.group_validator.is_empty() || args.forbid.is_some())
+        {
+            println!();
+        }
+
+        let mut violated = 0;
+        for target in &testcases {
+            let inflate_dir = TempDir::new()?;
+            let content = read(inflate_input(target, inflate_dir.path())?)?;
+            let checked = schema::check(&schema, &String::from_utf8_lossy(&content));
+            let mut result = CaseResult::new(target, "schema", None, checked.is_ok());
+            if let Err(err) = checked {
+                violated += 1;
+                result.message = Some(format!("{err:#}"));
+                if !json {
+                    println!("[SCHEMA] {target:?}: {err:#}");
+                }
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{violated} of {} case(s) violate the schema {schema_path:?}",
+                testcases.len()
+            );
+        }
+    }
+
+    if let Some(lints) = args.lint.as_ref().filter(|_| !stopped(&all_results)) {
+        let lints = if lints.is_empty() {
+            lint::Lint::all()
+        } else {
+            lints.clone()
+        };
+        if !json && !all_results.is_empty() {
+            println!();
+        }
+
+        let mut violated = 0;
+        for target in &testcases {
+            let violations = lint::check_case(target, &lints)?;
+            let mut result = CaseResult::new(target, "lint", None, violations.is_empty());
+            if !violations.is_empty() {
+                violated += 1;
+                result.message = Some(violations.join("\n"));
+            }
+            if !json {
+                for violation in &violations {
+                    println!("[LINT] {target:?}: {violation}");
+                }
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{violated} of {} case(s) have formatting issues",
+                testcases.len()
+            );
+        }
+    }
+
+    if let Some(pattern) = args
+        .name_pattern
+        .as_ref()
+        .filter(|_| !stopped(&all_results))
+    {
+        if !json && !all_results.is_empty() {
+            println!();
+        }
+
+        let mut violated = 0;
+        for target in &testcases {
+            let name = target.file_name().unwrap_or_default().to_string_lossy();
+            let matched = pattern.is_match(&name);
+            let mut result = CaseResult::new(target, "name", None, matched);
+            if !matched {
+                violated += 1;
+                let message = format!("{name:?} does not match {}", pattern.as_str());
+                if !json {
+                    println!("[NAME] {target:?}: {message}");
+                }
+                result.message = Some(message);
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{violated} of {} case(s) violate the naming convention",
+                testcases.len()
+            );
+        }
+    }
+
+    if args.duplicates && !stopped(&all_results) {
+        if !json && !all_results.is_empty() {
+            println!();
+        }
+
+        // ケース全体に対する検査なので，--only-failed, --sample で絞り込む前の全てのケースを調べる
+        let duplicates = find_duplicate_cases(&all_testcases)?;
+        for target in &all_testcases {
+            let original = duplicates
+                .iter()
+                .find(|(_, duplicate)| duplicate == target)
+                .map(|(original, _)| original);
+            let mut result = CaseResult::new(target, "duplicates", None, original.is_none());
+            if let Some(original) = original {
+                result.message = Some(format!("same as {original:?}"));
+                if !json {
+                    println!("[DUPLICATE] {target:?}: same as {original:?}");
+                }
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{} of {} case(s) duplicate an earlier case",
+                duplicates.len(),
+                all_testcases.len()
+            );
+        }
+    }
+
+    if args.orphans && !stopped(&all_results) {
+        if !json && !all_results.is_empty() {
+            println!();
+        }
+
+        // 対応は絞り込む前の全てのケースで調べ，結果は検証したケースについてのみ表示する
+        let all_inputs: Vec<PathBuf> = all_testcases
+            .iter()
+            .filter(|target| is_input(target))
+            .cloned()
+            .collect();
+        let inputs: Vec<PathBuf> = testcases
+            .iter()
+            .filter(|target| is_input(target))
+            .cloned()
+            .collect();
+        let answers: Vec<PathBuf> = if args.answer_dir.exists() {
+            find_files(&args.answer_dir, true)?
+                .into_iter()
+                .filter(|path| is_target(path, "ans"))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let (orphan_inputs, orphan_answers) = find_orphans(&all_inputs, &answers);
+        let orphan_inputs: Vec<&PathBuf> = orphan_inputs
+            .iter()
+            .filter(|input| inputs.contains(input))
+            .collect();
+
+        let mut reports = Vec::new();
+        for input in &inputs {
+            let orphan = orphan_inputs.contains(&input);
+            let mut result = CaseResult::new(input, "orphans", None, !orphan);
+            if orphan {
+                result.message = Some(format!("no matching .ans in {:?}", args.answer_dir));
+            }
+            reports.push(result);
+        }
+        for answer in &orphan_answers {
+            let mut result = CaseResult::new(answer, "orphans", None, false);
+            result.message = Some("no matching .in".to_string());
+            reports.push(result);
+        }
+        for result in reports {
+            if !json && !result.success {
+                println!(
+                    "[ORPHAN] {:?}: {}",
+                    result.case,
+                    result.message.as_deref().unwrap_or_default()
+                );
+            }
+            all_results.push(result);
+            if stopped(&all_results) {
+                break;
+            }
+        }
+        if !json {
+            println!(
+                "{} of {} case(s) have no answer, {} of {} answer(s) have no case",
+                orphan_inputs.len(),
+                inputs.len(),
+                orphan_answers.len(),
+                answers.len()
+            );
+        }
+    }
+
+    let coverages = if (!args.min_cases.is_empty() || args.require_max) && !stopped(&all_results) {
+        check_coverage(&all_testcases, &args.min_cases, args.require_max)?
+    } else {
+        Vec::new()
+    };
+    let under_covered = coverages
+        .iter()
+        .filter(|coverage| coverage.status != "OK")
+        .count();
+    if !json && !coverages.is_empty() {
+        if !all_results.is_empty() {
+            println!();
+        }
+        println!("{}", Table::new(&coverages));
+    }
+
+    if let Some(cache) = cache {
+        cache.save()?;
+    }
+
+    if !json && stopped(&all_results) {
+        println!();
+        println!("validation stopped at the first invalid case (--policy fail-break)");
+    }
+
+    // 何らかの検査で不正と判定されたケース
+    let failed: HashSet<&PathBuf> = all_results
+        .iter()
+        .filter(|result| !result.success)
+        .map(|result| &result.case)
+        .collect();
+    let checked: HashSet<&PathBuf> = all_results.iter().map(|result| &result.case).collect();
+    let previous = load_failed(&args.outdir)?.unwrap_or_default();
+    save_failed(&args.outdir, &merge_failed(previous, &checked, &failed))?;
+
+    let summaries = summarize(&all_results);
+    let clusters = cluster_failures(&all_results);
+    if let Some(ref path) = args.report {
+        report::write_report(
+            path,
+            &testcases,
+            &all_results,
+            &summaries,
+            &clusters,
+            &coverages,
+        )?;
+        info!("[REPORT] {path:?}");
+    }
+    if json {
+        #[derive(Serialize)]
+        struct Report<'a> {
+            cases: usize,
+            failed: usize,
+            summary: &'a [Summary],
+            failure_clusters: &'a [FailureCluster],
+            results: &'a [CaseResult],
+            coverage: &'a [Coverage],
+        }
+        let report = Report {
+            cases: testcases.len(),
+            failed: failed.len(),
+            summary: &summaries,
+            failure_clusters: &clusters,
+            results: &all_results,
+            coverage: &coverages,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if !summaries.is_empty() {
+        let checked: HashSet<&PathBuf> = all_results.iter().map(|result| &result.case).collect();
+        println!();
+        println!("{}", Table::new(&summaries));
+        let mut line = format!(
+            "{} passed, {} failed",
+            checked.len() - failed.len(),
+            failed.len()
+        );
+        if checked.len() < testcases.len() {
+            line += &format!(", {} not checked", testcases.len() - checked.len());
+        }
+        println!("{line} ({} case(s))", testcases.len());
+
+        if !clusters.is_empty() {
+            println!();
+            println!("{}", Table::new(&clusters));
+        }
+    }
+
+    // 検証器の不具合はケースの誤りとして扱わず，--no-fail でもエラーとして終了する
+    let validator_errors = all_results
+        .iter()
+        .filter(|result| result.status == VALIDATOR_ERROR)
+        .count();
+    ensure!(
+        validator_errors == 0,
+        "the validator crashed or could not run on {validator_errors} case(s)"
+    );
+    ensure!(
+        failed.is_empty() || args.no_fail,
+        "{} of {} case(s) failed validation",
+        failed.len(),
+        testcases.len()
+    );
+    ensure!(
+        under_covered == 0 || args.no_fail,
+        "{under_covered} group(s) do not have enough cases"
+    );
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_policy() {
+        assert!(!ValidatePolicy::All.should_break(false));
+        assert!(!ValidatePolicy::FailBreak.should_break(true));
+        assert!(ValidatePolicy::FailBreak.should_break(false));
+    }
+
+    #[test]
+    fn test_is_target() {
+        assert!(is_target(Path::new("a.in"), "in"));
+        assert!(is_target(Path::new("a.in.gz"), "in"));
+        assert!(!is_target(Path::new("a.ans"), "in"));
+        assert!(is_target(Path::new("a.ans"), "ans"));
+        assert!(!is_target(Path::new("a.in"), "ans"));
+
+        assert_eq!(target_name(Path::new("dir/a_000.in.gz")), "a_000");
+        assert_eq!(target_name(Path::new("dir/a_000.ans")), "a_000");
+    }
+
+    #[test]
+    fn test_val_path() {
+        let outdir = Path::new("out");
+        let testcases = vec![PathBuf::from("input")];
+        assert_eq!(
+            val_path(outdir, Path::new("input/a.in"), &testcases),
+            Path::new("out/a.val")
+        );
+        assert_eq!(
+            val_path(outdir, Path::new("input/subtask1/a.in.gz"), &testcases),
+            Path::new("out/subtask1/a.val")
+        );
+        assert_eq!(
+            val_path(outdir, Path::new("input/subtask2/a.in"), &testcases),
+            Path::new("out/subtask2/a.val")
+        );
+        // ケースのパスを直接指定した場合
+        let testcases = vec![PathBuf::from("input/subtask1/a.in")];
+        assert_eq!(
+            val_path(outdir, Path::new("input/subtask1/a.in"), &testcases),
+            Path::new("out/a.val")
+        );
+    }
+
+    #[test]
+    fn test_summarize() {
+        let a = Path::new("a_000.in");
+        let b = Path::new("b_000.in");
+        let results = vec![
+            CaseResult::new(a, "v.py", None, true),
+            CaseResult::new(b, "v.py", None, false),
+            CaseResult::new(a, "forbid", None, true),
+            CaseResult::new(b, "forbid", None, true),
+            CaseResult::new(b, "v1.py", Some("1"), false),
+        ];
+        let summary = |check: &str, passed, failed| Summary {
+            check: check.to_string(),
+            passed,
+            failed,
+        };
+        assert_eq!(
+            summarize(&results),
+            vec![
+                summary("v.py", 1, 1),
+                summary("forbid", 2, 0),
+                summary("v1.py (group 1)", 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let testcases: Vec<PathBuf> = (0..10)
+            .map(|i| PathBuf::from(format!("a_{i:03}.in")))
+            .collect();
+        let sampled = sample(testcases.clone(), 3, 42);
+        assert_eq!(sampled.len(), 3);
+        assert_eq!(sampled, sample(testcases.clone(), 3, 42));
+        // 元の順序を保つ
+        let mut sorted = sampled.clone();
+        sorted.sort();
+        assert_eq!(sampled, sorted);
+        assert_eq!(sample(testcases.clone(), 10, 42), testcases);
+    }
+
+    #[test]
+    fn test_load_params() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("params.toml");
+        write(&path, "MAX_N = 100000\nMODE = \"tree\"\n").unwrap();
+
+        let params = load_params(Some(&path), &[parse_param("MAX_N=10").unwrap()]).unwrap();
+        assert_eq!(
+            params,
+            vec![
+                ("MAX_N".to_string(), "10".to_string()),
+                ("MODE".to_string(), "tree".to_string()),
+            ]
+        );
+        assert_eq!(load_params(None, &[]).unwrap(), Vec::new());
+        assert!(parse_param("=1").is_err());
+
+        write(&path, "MAX_N = [1, 2]\n").unwrap();
+        assert!(load_params(Some(&path), &[]).is_err());
+    }
+
+    #[test]
+    fn test_find_duplicate_cases() {
+        let dir = TempDir::new().unwrap();
+        let a = dir.path().join("a_000.in");
+        let b = dir.path().join("b_000.in");
+        let sub = dir.path().join("sub");
+        create_dir_all(&sub).unwrap();
+        let c = sub.join("a_000.in");
+        write(&a, "1\n").unwrap();
+        write(&b, "2\n").unwrap();
+        write(&c, "1\n").unwrap();
+
+        assert_eq!(
+            find_duplicate_cases(&[a.clone(), b.clone(), c.clone()]).unwrap(),
+            vec![(a.clone(), c.clone())]
+        );
+        assert!(find_duplicate_cases(&[a, b]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_orphans() {
+        let inputs = vec![
+            PathBuf::from("input/a_000.in"),
+            PathBuf::from("input/b_000.in.gz"),
+            PathBuf::from("input/c_000.in"),
+        ];
+        let answers = vec![
+            PathBuf::from("answer/a_000.ans"),
+            PathBuf::from("answer/sub/b_000.ans"),
+            PathBuf::from("answer/d_000.ans"),
+        ];
+        assert_eq!(
+            find_orphans(&inputs, &answers),
+            (
+                vec![PathBuf::from("input/c_000.in")],
+                vec![PathBuf::from("answer/d_000.ans")]
+            )
+        );
+    }
+
+    #[test]
+    fn test_cluster_failures() {
+        let result = |case: &str, validator: &str, message: Option<&str>| {
+            let mut result = CaseResult::new(Path::new(case), validator, None, message.is_none());
+            result.message = message.map(String::from);
+            result
+        };
+        let results = vec![
+            result(
+                "a.in",
+                "v.py",
+                Some("n = 0 out of range (line 1)\nline 1: \"0\""),
+            ),
+            result("b.in", "v.py", None),
+            result("c.in", "forbid", Some("line 2: tab")),
+            result("d.in", "v.py", Some("n = 200001 out of range (line 1)")),
+        ];
+        let cluster = |check: &str, message: &str, cases, example: &str| FailureCluster {
+            check: check.to_string(),
+            message: message.to_string(),
+            cases,
+            example: format!("{:?}", Path::new(example)),
+        };
+        assert_eq!(
+            cluster_failures(&results),
+            vec![
+                cluster("v.py", "n = # out of range (line #)", 2, "a.in"),
+                cluster("forbid", "line #: tab", 1, "c.in"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validator_error() {
+        assert_eq!(
+            validator_error(ExecuteStatus::Fail, None, "wrong n", true),
+            None
+        );
+        assert_eq!(
+            validator_error(ExecuteStatus::TimeLimitExceed, None, "", false),
+            Some("time limit exceeded".to_string())
+        );
+        assert_eq!(
+            validator_error(
+                ExecuteStatus::OutputLimitExceed,
+                Some(libc::SIGXFSZ),
+                "",
+                false
+            ),
+            Some("output limit exceeded".to_string())
+        );
+        assert_eq!(
+            validator_error(ExecuteStatus::Fail, Some(libc::SIGSEGV), "", false),
+            Some("killed by signal 11 (SIGSEGV)".to_string())
+        );
+        let stderr = "Traceback (most recent call last):\nMemoryError\n";
+        assert_eq!(
+            validator_error(ExecuteStatus::Fail, None, stderr, true),
+            Some("memory limit exceeded".to_string())
+        );
+        assert_eq!(
+            validator_error(ExecuteStatus::Fail, None, stderr, false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_signal_name() {
+        assert_eq!(signal_name(libc::SIGSEGV), "signal 11 (SIGSEGV)");
+        assert_eq!(
+            signal_name(libc::SIGUSR1),
+            format!("signal {}", libc::SIGUSR1)
+        );
+    }
+
+    #[test]
+    fn test_failed() {
+        let dir = TempDir::new().unwrap();
+        let outdir = dir.path().join("validate");
+        assert_eq!(load_failed(&outdir).unwrap(), None);
+
+        let a = PathBuf::from("testcases/input/a_000.in");
+        save_failed(&outdir, std::slice::from_ref(&a)).unwrap();
+        assert_eq!(load_failed(&outdir).unwrap(), Some(vec![a.clone()]));
+        save_failed(&outdir, &[]).unwrap();
+        assert_eq!(load_failed(&outdir).unwrap(), Some(Vec::new()));
+
+        // 検査していないケースの記録は残り，検査したケースは今回の結果で置き換わる
+        let b = PathBuf::from("testcases/input/b_000.in");
+        let c = PathBuf::from("testcases/input/c_000.in");
+        let previous = vec![a.clone(), b.clone()];
+        assert_eq!(
+            merge_failed(previous.clone(), &HashSet::new(), &HashSet::new()),
+            vec![a.clone(), b.clone()]
+        );
+        assert_eq!(
+            merge_failed(previous, &HashSet::from([&b, &c]), &HashSet::from([&c])),
+            vec![a, c]
+        );
+    }
+
+    #[test]
+    fn test_in_group() {
+        let dir = TempDir::new().unwrap();
+        let subtask = dir.path().join("subtask1");
+        create_dir_all(&subtask).unwrap();
+        let target = subtask.join("random_000.in");
+        write(&target, "1\n").unwrap();
+
+        assert!(in_group(&target, "1").unwrap());
+        assert!(in_group(&target, "subtask1").unwrap());
+        assert!(!in_group(&target, "2").unwrap());
+
+        write(subtask.join("random_000.tags"), "group=large").unwrap();
+        assert!(in_group(&target, "large").unwrap());
+        assert!(!in_group(&target, "1").unwrap());
+
+        assert_eq!(
+            parse_group_validator("1=val.cpp").unwrap(),
+            ("1".to_string(), PathBuf::from("val.cpp"))
+        );
+        assert!(parse_group_validator("val.cpp").is_err());
+    }
+
+    #[test]
+    fn test_input_snippet() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("a.in");
+        write(&target, format!("3\n1 2 30\n{}\n", "9".repeat(50))).unwrap();
+
+        let message =
+            "FAIL Integer element a[3] equals to 30, violates the range [1, 10] (stdin, line 2)";
+        assert_eq!(
+            input_snippet(&target, message).unwrap(),
+            Some("line 2: \"1 2 30\"".to_string())
+        );
+        assert_eq!(
+            input_snippet(&target, "FAIL (stdin, line 3)").unwrap(),
+            Some(format!("line 3: \"{}...\"", "9".repeat(SNIPPET_WIDTH)))
+        );
+        assert_eq!(
+            input_snippet(&target, "FAIL (stdin, line 10)").unwrap(),
+            Some("line 10: <EOF>".to_string())
+        );
+        assert_eq!(input_snippet(&target, "FAIL").unwrap(), None);
+    }
+
+    #[test]
+    fn test_check_coverage() {
+        let dir = TempDir::new().unwrap();
+        let mut testcases = Vec::new();
+        for (subtask, name) in [("subtask1", "a"), ("subtask1", "b"), ("subtask2", "c")] {
+            let subtask = dir.path().join(subtask);
+            create_dir_all(&subtask).unwrap();
+            let target = subtask.join(format!("{name}.in"));
+            write(&target, "1\n").unwrap();
+            testcases.push(target);
+        }
+        write(dir.path().join("subtask1/b.tags"), "max").unwrap();
+
+        let coverages = check_coverage(&testcases, &[], true).unwrap();
+        assert_eq!(coverages.len(), 2);
+        assert_eq!(
+            (
+                coverages[0].group.as_str(),
+                coverages[0].cases,
+                coverages[0].max_cases
+            ),
+            ("1", 2, 1)
+        );
+        assert_eq!(coverages[0].status, "OK");
+        assert_eq!(coverages[1].status, "FAIL: no max case");
+
+        let coverages = check_coverage(
+            &testcases,
+            &[("subtask2".to_string(), 2), ("3".to_string(), 0)],
+            false,
+        )
+        .unwrap();
+        assert_eq!(coverages[0].status, "FAIL: fewer than 2 case(s)");
+        assert_eq!(coverages[1].cases, 0);
+        assert_eq!(coverages[1].status, "OK");
+
+        assert_eq!(parse_min_cases("1=5").unwrap(), ("1".to_string(), 5));
+        assert!(parse_min_cases("1").is_err());
+        assert!(parse_min_cases("1=x").is_err());
+    }
+
+    #[test]
+    fn test_count_by_dir() {
+        let testcases = [
+            PathBuf::from("input/subtask2/a.in"),
+            PathBuf::from("input/b.in"),
+            PathBuf::from("input/subtask2/c.in"),
+        ];
+        assert_eq!(
+            count_by_dir(&testcases),
+            vec![
+                (PathBuf::from("input"), 1),
+                (PathBuf::from("input/subtask2"), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_context_args() {
+        let dir = TempDir::new().unwrap();
+        let subtask = dir.path().join("subtask2");
+        create_dir_all(&subtask).unwrap();
+        let target = subtask.join("random_000.in");
+        write(&target, "1\n").unwrap();
+
+        assert!(context_args(&target, false, None).unwrap().is_empty());
+        assert_eq!(
+            context_args(&target, true, Some("tests")).unwrap(),
+            vec!["--group", "2", "--testset", "tests"]
+        );
+
+        write(
+            subtask.join("random_000.tags"),
+            "max group=g1 testset=pretests",
+        )
+        .unwrap();
+        assert_eq!(
+            context_args(&target, true, Some("tests")).unwrap(),
+            vec!["--group", "g1", "--testset", "pretests"]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::generate::manifest::source_hash;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+/// 検証に通った (検証器, ケース, 検証器への引数) の組を記録する outdir/cache.json
+/// 検証器とケースは内容のハッシュで識別するため，変更されたファイルは再検証される
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    passed: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub(super) struct Cache {
+    path: PathBuf,
+    passed: BTreeSet<String>,
+}
+
+impl Cache {
+    pub(super) fn load(outdir: &Path) -> Result<Self> {
+        let path = outdir.join("cache.json");
+        let passed = match read_to_string(&path) {
+            Ok(content) => {
+                serde_json::from_str::<CacheFile>(&content)
+                    .with_context(|| format!("failed to parse {path:?}"))?
+                    .passed
+            }
+            Err(_) => BTreeSet::new(),
+        };
+        Ok(Cache { path, passed })
+    }
+
+    /// validator_hash は検証器のソースの SHA-256
+    pub(super) fn key(validator_hash: &str, case: &Path, context: &[String]) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(validator_hash);
+        hasher.update("\n");
+        hasher.update(source_hash(case)?);
+        for arg in context {
+            hasher.update("\n");
+            hasher.update(arg);
+        }
+        let digest = hasher.finalize();
+        Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    pub(super) fn contains(&self, key: &str) -> bool {
+        self.passed.contains(key)
+    }
+
+    pub(super) fn insert(&mut self, key: String) {
+        self.passed.insert(key);
+    }
+
+    pub(super) fn save(self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent)?;
+        }
+        let cache = CacheFile {
+            passed: self.passed,
+        };
+        write(&self.path, serde_json::to_string_pretty(&cache)? + "\n")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let case = dir.path().join("a_000.in");
+        write(&case, "1\n").unwrap();
+
+        let outdir = dir.path().join("validate");
+        let mut cache = Cache::load(&outdir).unwrap();
+        let key = Cache::key("hash", &case, &[]).unwrap();
+        assert!(!cache.contains(&key));
+        cache.insert(key.clone());
+        cache.save().unwrap();
+
+        let cache = Cache::load(&outdir).unwrap();
+        assert!(cache.contains(&key));
+        // 検証器，引数，ケースの内容のいずれかが変われば別の組となる
+        assert!(!cache.contains(&Cache::key("other", &case, &[]).unwrap()));
+        assert!(!cache.contains(&Cache::key("hash", &case, &["--group".to_string()]).unwrap()));
+        write(&case, "2\n").unwrap();
+        assert!(!cache.contains(&Cache::key("hash", &case, &[]).unwrap()));
+    }
+}
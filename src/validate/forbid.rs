@@ -0,0 +1,116 @@
+use crate::utils::inflate_input;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs::{read, read_to_string};
+use std::path::Path;
+use tempfile::TempDir;
+
+/// 禁止パターンの設定ファイル（toml）
+/// [[forbid]] ごとに 1 つの正規表現を表す
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    forbid: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    /// 各行（改行文字を除く）に対して適用する正規表現
+    pattern: String,
+    /// 違反したときに表示するメッセージ（省略時は pattern）
+    message: Option<String>,
+}
+
+#[derive(Debug)]
+pub(super) struct Pattern {
+    regex: Regex,
+    message: String,
+}
+
+/// 設定ファイルを読み込み，禁止パターンの一覧を返す
+pub(super) fn load_patterns(path: &Path) -> Result<Vec<Pattern>> {
+    let content = read_to_string(path).with_context(|| format!("failed to read {path:?}"))?;
+    let config: Config =
+        toml::from_str(&content).with_context(|| format!("failed to parse {path:?}"))?;
+
+    config
+        .forbid
+        .into_iter()
+        .map(|rule| {
+            let regex = Regex::new(&rule.pattern)
+                .with_context(|| format!("invalid pattern {:?} in {path:?}", rule.pattern))?;
+            Ok(Pattern {
+                message: rule.message.unwrap_or(rule.pattern),
+                regex,
+            })
+        })
+        .collect()
+}
+
+/// content の各行を検査し，違反ごとに "line N: message" を返す
+/// CR を検出できるよう，行は \n のみで区切る
+fn check_content(content: &str, patterns: &[Pattern]) -> Vec<String> {
+    let mut violations = Vec::new();
+    for pattern in patterns {
+        let line = content
+            .split('\n')
+            .position(|line| pattern.regex.is_match(line));
+        if let Some(line) = line {
+            violations.push(format!("line {}: {}", line + 1, pattern.message));
+        }
+    }
+    violations
+}
+
+/// case を検査し，違反の一覧を返す（パターンごとに最初の違反のみ）
+pub(super) fn check_case(case: &Path, patterns: &[Pattern]) -> Result<Vec<String>> {
+    let inflate_dir = TempDir::new()?;
+    let content = read(inflate_input(case, inflate_dir.path())?)?;
+    Ok(check_content(&String::from_utf8_lossy(&content), patterns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    #[test]
+    fn test_check_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("forbid.toml");
+        write(
+            &path,
+            r#"
+[[forbid]]
+pattern = '\t'
+message = "tab"
+
+[[forbid]]
+pattern = '\r'
+message = "CR"
+
+[[forbid]]
+pattern = '(^| )0\d'
+"#,
+        )
+        .unwrap();
+        let patterns = load_patterns(&path).unwrap();
+        assert_eq!(patterns.len(), 3);
+
+        assert!(check_content("3\n1 2 3\n", &patterns).is_empty());
+        assert_eq!(
+            check_content("3\r\n1\t2 03\n", &patterns),
+            vec![
+                "line 2: tab".to_string(),
+                "line 1: CR".to_string(),
+                r"line 2: (^| )0\d".to_string(),
+            ]
+        );
+
+        write(&path, "[[forbid]]\npattern = '('\n").unwrap();
+        assert!(load_patterns(&path).is_err());
+    }
+}
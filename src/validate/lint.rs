@@ -0,0 +1,199 @@
+use crate::utils::inflate_input;
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fs::read;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// 組み込みの書式検査
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub(super) enum Lint {
+    /// Spaces or tabs at the end of a line
+    TrailingWhitespace,
+
+    /// CRLF line endings
+    Crlf,
+
+    /// No newline at the end of the file
+    FinalNewline,
+
+    /// Bytes outside ASCII
+    NonAscii,
+
+    /// Empty file
+    Empty,
+
+    /// Tokens nan or inf (numeric check, e.g. for answers)
+    NanInf,
+
+    /// Numbers in exponent notation such as 1e-9 (numeric check)
+    Exponent,
+
+    /// Decimals with a different number of digits after the point than the first one (numeric check)
+    DecimalPlaces,
+}
+
+impl Lint {
+    /// 値を省略した --lint で行う検査（数値の検査は明示的に指定したときのみ行う）
+    pub(super) fn all() -> Vec<Lint> {
+        Lint::value_variants()
+            .iter()
+            .copied()
+            .filter(|lint| !lint.is_numeric())
+            .collect()
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Lint::NanInf | Lint::Exponent | Lint::DecimalPlaces)
+    }
+}
+
+/// 小数点以下の桁数（固定小数点表記の小数でなければ None）
+fn decimal_places(token: &str) -> Option<usize> {
+    let (int, frac) = token
+        .strip_prefix(['+', '-'])
+        .unwrap_or(token)
+        .split_once('.')?;
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|byte| byte.is_ascii_digit());
+    (is_digits(int) && is_digits(frac)).then_some(frac.len())
+}
+
+/// 指数表記の数（1e9，-2.5E-3 など）かどうか
+fn is_exponent(token: &str) -> bool {
+    let Some((mantissa, exponent)) = token.split_once(['e', 'E']) else {
+        return false;
+    };
+    let mantissa = mantissa.strip_prefix(['+', '-']).unwrap_or(mantissa);
+    let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+    let is_digits = |s: &str| s.bytes().all(|byte| byte.is_ascii_digit());
+    let mantissa_ok = match mantissa.split_once('.') {
+        Some((int, frac)) => {
+            !(int.is_empty() && frac.is_empty()) && is_digits(int) && is_digits(frac)
+        }
+        None => !mantissa.is_empty() && is_digits(mantissa),
+    };
+    mantissa_ok && !exponent.is_empty() && is_digits(exponent)
+}
+
+fn is_nan_inf(token: &str) -> bool {
+    let token = token
+        .strip_prefix(['+', '-'])
+        .unwrap_or(token)
+        .to_ascii_lowercase();
+    matches!(token.as_str(), "nan" | "inf" | "infinity")
+}
+
+/// content を検査し，違反ごとにメッセージを返す（検査ごとに最初の違反のみ）
+fn check_content(content: &[u8], lints: &[Lint]) -> Vec<String> {
+    let mut violations = Vec::new();
+    let lines: Vec<&[u8]> = content.split(|&byte| byte == b'\n').collect();
+    let position = |pred: &dyn Fn(&[u8]) -> bool| lines.iter().position(|line| pred(line));
+    // 空白区切りのトークンのうち，最初に pred を満たすものの行番号（0-indexed）とトークン
+    let token_position = |pred: &dyn Fn(&str) -> bool| {
+        lines.iter().enumerate().find_map(|(i, line)| {
+            String::from_utf8_lossy(line)
+                .split_whitespace()
+                .find(|token| pred(token))
+                .map(|token| (i, token.to_string()))
+        })
+    };
+
+    for lint in lints {
+        let violation = match lint {
+            Lint::TrailingWhitespace => position(&|line| {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                line.last()
+                    .is_some_and(|&byte| byte == b' ' || byte == b'\t')
+            })
+            .map(|i| format!("line {}: trailing whitespace", i + 1)),
+            Lint::Crlf => position(&|line| line.ends_with(b"\r"))
+                .map(|i| format!("line {}: CRLF line ending", i + 1)),
+            Lint::FinalNewline => (!content.is_empty() && !content.ends_with(b"\n"))
+                .then(|| "no newline at end of file".to_string()),
+            Lint::NonAscii => position(&|line| !line.is_ascii())
+                .map(|i| format!("line {}: non-ASCII byte", i + 1)),
+            Lint::Empty => content.is_empty().then(|| "empty file".to_string()),
+            Lint::NanInf => token_position(&is_nan_inf)
+                .map(|(i, token)| format!("line {}: {token:?} is not a finite number", i + 1)),
+            Lint::Exponent => token_position(&is_exponent)
+                .map(|(i, token)| format!("line {}: {token:?} is in exponent notation", i + 1)),
+            Lint::DecimalPlaces => token_position(&|token| decimal_places(token).is_some())
+                .and_then(|(first_line, first)| {
+                    let places = decimal_places(&first).unwrap();
+                    token_position(&|token| {
+                        decimal_places(token).is_some_and(|other| other != places)
+                    })
+                    .map(|(i, token)| {
+                        format!(
+                            "line {}: {token:?} has {} decimal place(s), but {first:?} on line {} has {places}",
+                            i + 1,
+                            decimal_places(&token).unwrap(),
+                            first_line + 1
+                        )
+                    })
+                }),
+        };
+        violations.extend(violation);
+    }
+    violations
+}
+
+/// case を検査し，違反の一覧を返す
+pub(super) fn check_case(case: &Path, lints: &[Lint]) -> Result<Vec<String>> {
+    let inflate_dir = TempDir::new()?;
+    let content = read(inflate_input(case, inflate_dir.path())?)?;
+    Ok(check_content(&content, lints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_content() {
+        let all = Lint::all();
+        assert!(check_content(b"3\n1 2 3\n", &all).is_empty());
+        assert_eq!(check_content(b"", &all), vec!["empty file".to_string()]);
+        assert_eq!(
+            check_content("3 \r\n1 2\t3\n\u{3042}".as_bytes(), &all),
+            vec![
+                "line 1: trailing whitespace".to_string(),
+                "line 1: CRLF line ending".to_string(),
+                "no newline at end of file".to_string(),
+                "line 3: non-ASCII byte".to_string(),
+            ]
+        );
+        assert_eq!(
+            check_content(b"1 \n2", &[Lint::Crlf, Lint::Empty]),
+            Vec::<String>::new()
+        );
+        // 数値の検査は値を省略した --lint では行わない
+        assert!(check_content(b"nan 1e9\n", &all).is_empty());
+    }
+
+    #[test]
+    fn test_check_numeric() {
+        let numeric = [Lint::NanInf, Lint::Exponent, Lint::DecimalPlaces];
+        assert!(check_content(b"3\n0.500000 -1.250000\n", &numeric).is_empty());
+        assert_eq!(
+            check_content(b"2\n0.500 -Inf\n1.5E-3 0.25\n", &numeric),
+            vec![
+                "line 2: \"-Inf\" is not a finite number".to_string(),
+                "line 3: \"1.5E-3\" is in exponent notation".to_string(),
+                "line 3: \"0.25\" has 2 decimal place(s), but \"0.500\" on line 2 has 3"
+                    .to_string(),
+            ]
+        );
+
+        assert_eq!(decimal_places("-0.125"), Some(3));
+        assert_eq!(decimal_places("10"), None);
+        assert_eq!(decimal_places("1."), None);
+        assert!(is_exponent("1e9"));
+        assert!(is_exponent(".5e+3"));
+        assert!(!is_exponent("e9"));
+        assert!(!is_exponent("1e"));
+        assert!(!is_exponent("tree"));
+        assert!(is_nan_inf("NaN"));
+        assert!(!is_nan_inf("info"));
+    }
+}
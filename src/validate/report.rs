@@ -0,0 +1,237 @@
+use super::{case_group, CaseResult, Coverage, FailureCluster, Summary};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fs::write;
+use std::path::{Path, PathBuf};
+
+/// レポートの 1 つの表
+struct Section {
+    title: String,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Section {
+    fn new(title: &str, header: &[&str]) -> Self {
+        Section {
+            title: title.to_string(),
+            header: header.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+/// HTML の特殊文字をエスケープする
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Markdown の表のセル（HTML のタグとして解釈されないよう < > & を，表を崩さないよう | と改行を置き換える）
+fn markdown_cell(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('|', "\\|")
+        .replace('\n', "<br>")
+}
+
+fn render_markdown(overview: &str, sections: &[Section]) -> String {
+    let mut md = format!("# kuroe validate report\n\n{overview}\n");
+    for section in sections {
+        md.push_str(&format!("\n## {}\n\n", section.title));
+        md.push_str(&format!("| {} |\n", section.header.join(" | ")));
+        md.push_str(&format!("|{}\n", " --- |".repeat(section.header.len())));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(|cell| markdown_cell(cell)).collect();
+            md.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+    }
+    md
+}
+
+fn render_html(overview: &str, sections: &[Section]) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>kuroe validate report</title>\n");
+    html.push_str("<style>\nbody { font-family: sans-serif; }\ntable { border-collapse: collapse; }\nth, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; vertical-align: top; }\ntd { white-space: pre-wrap; }\n</style>\n</head>\n<body>\n");
+    html.push_str(&format!(
+        "<h1>kuroe validate report</h1>\n<p>{}</p>\n",
+        escape_html(overview)
+    ));
+    for section in sections {
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<table>\n<tr>",
+            escape_html(&section.title)
+        ));
+        for column in &section.header {
+            html.push_str(&format!("<th>{}</th>", escape_html(column)));
+        }
+        html.push_str("</tr>\n");
+        for row in &section.rows {
+            html.push_str("<tr>");
+            for cell in row {
+                html.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// 検査ごと，グループごとの集計と不正なケースの一覧からなる表を作る
+fn sections(
+    testcases: &[PathBuf],
+    results: &[CaseResult],
+    summaries: &[Summary],
+    clusters: &[FailureCluster],
+    coverages: &[Coverage],
+) -> Result<Vec<Section>> {
+    let mut sections = Vec::new();
+
+    let mut summary = Section::new("Checks", &["check", "passed", "failed"]);
+    for s in summaries {
+        summary.rows.push(vec![
+            s.check.clone(),
+            s.passed.to_string(),
+            s.failed.to_string(),
+        ]);
+    }
+    sections.push(summary);
+
+    // グループごとのケース数と不正なケース数（グループのないケースは "-"）
+    let mut groups: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for target in testcases {
+        let group = case_group(target)?.unwrap_or_else(|| "-".to_string());
+        let failed = results
+            .iter()
+            .any(|result| result.case == *target && !result.success);
+        let entry = groups.entry(group).or_default();
+        entry.0 += 1;
+        if failed {
+            entry.1 += 1;
+        }
+    }
+    let mut group_section = Section::new("Groups", &["group", "cases", "failed"]);
+    for (group, (cases, failed)) in groups {
+        group_section
+            .rows
+            .push(vec![group, cases.to_string(), failed.to_string()]);
+    }
+    sections.push(group_section);
+
+    if !coverages.is_empty() {
+        let mut coverage = Section::new("Coverage", &["group", "cases", "max_cases", "status"]);
+        for c in coverages {
+            coverage.rows.push(vec![
+                c.group.clone(),
+                c.cases.to_string(),
+                c.max_cases.to_string(),
+                c.status.clone(),
+            ]);
+        }
+        sections.push(coverage);
+    }
+
+    if !clusters.is_empty() {
+        let mut cluster_section = Section::new(
+            "Failure clusters",
+            &["check", "message", "cases", "example"],
+        );
+        for c in clusters {
+            cluster_section.rows.push(vec![
+                c.check.clone(),
+                c.message.clone(),
+                c.cases.to_string(),
+                c.example.clone(),
+            ]);
+        }
+        sections.push(cluster_section);
+
+        let mut failures = Section::new("Failures", &["check", "case", "status", "message"]);
+        for result in results.iter().filter(|result| !result.success) {
+            let check = match result.group {
+                Some(ref group) => format!("{} (group {group})", result.validator),
+                None => result.validator.clone(),
+            };
+            failures.rows.push(vec![
+                check,
+                result.case.to_string_lossy().to_string(),
+                result.status.clone(),
+                result.message.clone().unwrap_or_default(),
+            ]);
+        }
+        sections.push(failures);
+    }
+
+    Ok(sections)
+}
+
+/// path に検証のレポートを書き込む（拡張子が .html または .htm なら HTML，それ以外は Markdown）
+pub(super) fn write_report(
+    path: &Path,
+    testcases: &[PathBuf],
+    results: &[CaseResult],
+    summaries: &[Summary],
+    clusters: &[FailureCluster],
+    coverages: &[Coverage],
+) -> Result<()> {
+    let failed = testcases
+        .iter()
+        .filter(|target| {
+            results
+                .iter()
+                .any(|result| result.case == **target && !result.success)
+        })
+        .count();
+    let overview = format!(
+        "{} case(s), {} passed, {failed} failed",
+        testcases.len(),
+        testcases.len() - failed
+    );
+
+    let sections = sections(testcases, results, summaries, clusters, coverages)?;
+    let report = if path
+        .extension()
+        .is_some_and(|ext| ext == "html" || ext == "htm")
+    {
+        render_html(&overview, &sections)
+    } else {
+        render_markdown(&overview, &sections)
+    };
+    write(path, report)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut section = Section::new("Failures", &["case", "message"]);
+        section
+            .rows
+            .push(vec!["a.in".to_string(), "n < 0 | bad\nline 1".to_string()]);
+
+        assert_eq!(
+            render_markdown("1 case(s)", &[section]),
+            "# kuroe validate report\n\n1 case(s)\n\n## Failures\n\n| case | message |\n| --- | --- |\n| a.in | n &lt; 0 \\| bad<br>line 1 |\n"
+        );
+
+        let mut section = Section::new("Failures", &["case"]);
+        section.rows.push(vec!["<a>".to_string()]);
+        let html = render_html("1 case(s)", &[section]);
+        assert!(html.contains("<h2>Failures</h2>\n<table>\n<tr><th>case</th></tr>\n<tr><td>&lt;a&gt;</td></tr>\n</table>\n"));
+    }
+}